@@ -0,0 +1,758 @@
+//! Collaborative component trees
+//!
+//! `CollaborativeTree` is a CRDT that lets several replicas edit the same
+//! component tree concurrently and converge without a central arbiter.
+//!
+//! Every node is identified by the operation that inserted it
+//! (`"<seq>@<replica>"`, or `"root"`). Children form an RGA sequence: an
+//! insert names its left neighbour at the time, and concurrent inserts after
+//! the same neighbour are ordered newest first by Lamport timestamp, then
+//! replica id. Removed children stay as tombstones so that concurrent
+//! inserts next to them still find their place. The component type and each
+//! prop are last-writer-wins registers ordered the same way.
+//!
+//! Local edits return the operations to broadcast as JSON. Remote operations
+//! may arrive out of order or more than once: each replica's operations are
+//! applied in sequence, operations whose dependencies are missing wait in a
+//! buffer, and duplicates are dropped. The state vector (highest sequence
+//! number applied per replica) lets a peer that was offline ask for exactly
+//! the operations it is missing with `sync_message`.
+//!
+//! An operation that no honest replica produces (an insert into a text node
+//! or after a node that is not a child of its parent, or a type or prop
+//! edit of a text node) is dropped once it is ready and counted by
+//! `dropped_count`. It still takes its place in the state vector and the
+//! log, so every replica drops it alike and the sender's later operations
+//! are not held up.
+//!
+//! `to_json` produces a `SerializedComponent`, with props as a JSON string.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use crate::SerializedComponent;
+
+/// Identity of an operation, and of the node an insert creates
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct OpId {
+    replica: String,
+    /// Position in the replica's own operations, starting at 1
+    seq: u64,
+}
+
+impl OpId {
+    fn root() -> Self {
+        Self { replica: String::new(), seq: 0 }
+    }
+}
+
+impl fmt::Display for OpId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.seq == 0 {
+            f.write_str("root")
+        } else {
+            write!(f, "{}@{}", self.seq, self.replica)
+        }
+    }
+}
+
+impl FromStr for OpId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        if s == "root" {
+            return Ok(Self::root());
+        }
+        s.split_once('@')
+            .and_then(|(seq, replica)| {
+                let seq = seq.parse().ok().filter(|&seq| seq > 0)?;
+                (!replica.is_empty()).then(|| Self { replica: replica.to_string(), seq })
+            })
+            .ok_or_else(|| format!("invalid node id \"{}\"", s))
+    }
+}
+
+impl Serialize for OpId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OpId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Total order of operations: Lamport timestamp, then replica id
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Stamp {
+    lamport: u64,
+    replica: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum OpKind {
+    /// Insert a component, or a text node if `text` is set, into `parent`
+    /// right after the child `after` (first if `None`)
+    Insert {
+        parent: OpId,
+        after: Option<OpId>,
+        #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+        component_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        text: Option<String>,
+    },
+    Remove {
+        target: OpId,
+    },
+    SetType {
+        target: OpId,
+        #[serde(rename = "type")]
+        component_type: String,
+    },
+    SetProp {
+        target: OpId,
+        key: String,
+        value: Value,
+    },
+    RemoveProp {
+        target: OpId,
+        key: String,
+    },
+}
+
+/// One edit, as broadcast to other replicas:
+/// `{ "id": "3@alice", "lamport": 7, "op": "set_prop", "target": "root", "key": "title", "value": "Hi" }`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Op {
+    id: OpId,
+    lamport: u64,
+    #[serde(flatten)]
+    kind: OpKind,
+}
+
+#[derive(Clone, Debug)]
+struct Register<T> {
+    value: T,
+    stamp: Stamp,
+}
+
+impl<T> Register<T> {
+    fn set(&mut self, value: T, stamp: &Stamp) {
+        if *stamp > self.stamp {
+            self.value = value;
+            self.stamp = stamp.clone();
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// Text content; `None` for components
+    text: Option<String>,
+    component_type: Register<String>,
+    /// `None` marks a removed prop
+    props: BTreeMap<String, Register<Option<Value>>>,
+    /// Every child ever inserted, tombstones included, in sequence order
+    children: Vec<OpId>,
+    /// Stamp of the insert, which orders concurrent siblings
+    stamp: Stamp,
+    removed: bool,
+}
+
+impl Node {
+    fn new(text: Option<String>, component_type: String, stamp: Stamp) -> Self {
+        Self {
+            text,
+            component_type: Register { value: component_type, stamp: stamp.clone() },
+            props: BTreeMap::new(),
+            children: Vec::new(),
+            stamp,
+            removed: false,
+        }
+    }
+}
+
+/// A tree to insert, validated before any operation is generated
+enum TreeInput {
+    Text(String),
+    Component {
+        component_type: String,
+        props: serde_json::Map<String, Value>,
+        children: Vec<TreeInput>,
+    },
+}
+
+impl TreeInput {
+    /// A text string or `{ type, props?, children? }`; props may also be a
+    /// JSON string as in `SerializedComponent`
+    fn parse(tree: &Value) -> Result<Self, String> {
+        let Value::Object(component) = tree else {
+            return match tree {
+                Value::String(text) => Ok(Self::Text(text.clone())),
+                _ => Err("a node must be a component object or a text string".to_string()),
+            };
+        };
+        let component_type = component
+            .get("type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| "a component must have a string \"type\"".to_string())?
+            .to_string();
+        let props = match component.get("props") {
+            None | Some(Value::Null) => serde_json::Map::new(),
+            Some(Value::Object(props)) => props.clone(),
+            Some(Value::String(json)) => {
+                serde_json::from_str(json).map_err(|e| format!("invalid props of {}: {}", component_type, e))?
+            }
+            Some(_) => return Err(format!("props of {} must be an object", component_type)),
+        };
+        let children = match component.get("children") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(children)) => children.iter().map(Self::parse).collect::<Result<_, _>>()?,
+            Some(_) => return Err(format!("children of {} must be an array", component_type)),
+        };
+        Ok(Self::Component { component_type, props, children })
+    }
+}
+
+/// Component tree replica that converges with its peers
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct CollaborativeTree {
+    replica: String,
+    lamport: u64,
+    nodes: HashMap<OpId, Node>,
+    /// Highest sequence number applied per replica
+    state_vector: BTreeMap<String, u64>,
+    /// Applied operations in causal order, for sync messages
+    log: Vec<Op>,
+    /// Remote operations waiting for their dependencies
+    pending: Vec<Op>,
+    /// Remote operations dropped as invalid
+    dropped: usize,
+}
+
+#[wasm_bindgen]
+impl CollaborativeTree {
+    /// An empty tree; `replica_id` must be unique among the editing clients
+    #[wasm_bindgen(constructor)]
+    pub fn new(replica_id: String) -> Result<CollaborativeTree, JsValue> {
+        if replica_id.is_empty() {
+            return Err(JsValue::from_str("Replica id must not be empty"));
+        }
+        Ok(Self::with_replica(replica_id))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn replica_id(&self) -> String {
+        self.replica.clone()
+    }
+
+    /// Current tree as a `SerializedComponent`: `{ type, props, children }`
+    /// with props as a JSON string and text children as strings
+    pub fn to_json(&self) -> String {
+        self.component_tree(&OpId::root()).to_json()
+    }
+
+    /// Id of the child at an index, for addressing later edits
+    pub fn child_id(&self, parent_id: &str, index: usize) -> Option<String> {
+        let parent = parent_id.parse().ok()?;
+        self.visible_children(&parent).nth(index).map(OpId::to_string)
+    }
+
+    /// Set the root's type and props and append its children from a tree.
+    /// Meant for the replica that creates the document; the others start
+    /// empty and sync.
+    pub fn load_json(&mut self, json: &str) -> Result<String, JsValue> {
+        Self::ops_json(self.local_load(json))
+    }
+
+    /// Insert a component (or text string) as the child at `index`
+    pub fn insert_child(&mut self, parent_id: &str, index: usize, child_json: &str) -> Result<String, JsValue> {
+        Self::ops_json(self.local_insert_child(parent_id, index, child_json))
+    }
+
+    pub fn remove_child(&mut self, parent_id: &str, index: usize) -> Result<String, JsValue> {
+        Self::ops_json(self.local_remove_child(parent_id, index))
+    }
+
+    pub fn set_type(&mut self, node_id: &str, component_type: &str) -> Result<String, JsValue> {
+        let kind = |target| OpKind::SetType { target, component_type: component_type.to_string() };
+        Self::ops_json(self.local_edit(node_id, kind))
+    }
+
+    /// Set a prop to a JSON value
+    pub fn set_prop(&mut self, node_id: &str, key: &str, value_json: &str) -> Result<String, JsValue> {
+        let value: Value = serde_json::from_str(value_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse prop value: {}", e)))?;
+        Self::ops_json(self.local_edit(node_id, |target| OpKind::SetProp { target, key: key.to_string(), value }))
+    }
+
+    pub fn remove_prop(&mut self, node_id: &str, key: &str) -> Result<String, JsValue> {
+        Self::ops_json(self.local_edit(node_id, |target| OpKind::RemoveProp { target, key: key.to_string() }))
+    }
+
+    /// Apply a JSON array of operations from other replicas. Returns how
+    /// many were applied, including buffered ones they unblocked.
+    pub fn apply_remote(&mut self, ops_json: &str) -> Result<usize, JsValue> {
+        let ops: Vec<Op> = serde_json::from_str(ops_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse operations: {}", e)))?;
+        Ok(self.receive(ops))
+    }
+
+    /// Remote operations still waiting for their dependencies
+    #[wasm_bindgen(getter)]
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Remote operations dropped because they do not fit the tree
+    #[wasm_bindgen(getter)]
+    pub fn dropped_count(&self) -> usize {
+        self.dropped
+    }
+
+    /// Highest applied sequence number per replica: `{ "alice": 12 }`
+    #[wasm_bindgen(getter)]
+    pub fn state_vector_json(&self) -> String {
+        serde_json::to_string(&self.state_vector).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Operations a peer with the given state vector is missing, as a JSON
+    /// array for its `apply_remote`
+    pub fn sync_message(&self, state_vector_json: &str) -> Result<String, JsValue> {
+        let state_vector: BTreeMap<String, u64> = serde_json::from_str(state_vector_json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse state vector: {}", e)))?;
+        Ok(serde_json::to_string(&self.missing_ops(&state_vector)).unwrap_or_else(|_| "[]".to_string()))
+    }
+}
+
+impl CollaborativeTree {
+    fn with_replica(replica: String) -> Self {
+        let mut nodes = HashMap::new();
+        nodes.insert(OpId::root(), Node::new(None, String::new(), Stamp::default()));
+        Self {
+            replica,
+            lamport: 0,
+            nodes,
+            state_vector: BTreeMap::new(),
+            log: Vec::new(),
+            pending: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    fn ops_json(ops: Result<Vec<Op>, String>) -> Result<String, JsValue> {
+        ops.map(|ops| serde_json::to_string(&ops).unwrap_or_else(|_| "[]".to_string()))
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    fn seen(&self, replica: &str) -> u64 {
+        self.state_vector.get(replica).copied().unwrap_or(0)
+    }
+
+    fn visible_children<'a>(&'a self, parent: &OpId) -> impl Iterator<Item = &'a OpId> + 'a {
+        self.nodes
+            .get(parent)
+            .into_iter()
+            .flat_map(|node| node.children.iter())
+            .filter(|id| self.nodes.get(*id).is_some_and(|node| !node.removed))
+    }
+
+    /// A component that local edits may address
+    fn component(&self, id: &str) -> Result<OpId, String> {
+        let id: OpId = id.parse()?;
+        match self.nodes.get(&id) {
+            Some(node) if node.removed => Err(format!("node {} was removed", id)),
+            Some(node) if node.text.is_some() => Err(format!("node {} is a text node", id)),
+            Some(_) => Ok(id),
+            None => Err(format!("unknown node {}", id)),
+        }
+    }
+
+    /// Apply a new local operation
+    fn local(&mut self, kind: OpKind) -> Op {
+        let op = Op {
+            id: OpId { replica: self.replica.clone(), seq: self.seen(&self.replica) + 1 },
+            lamport: self.lamport + 1,
+            kind,
+        };
+        self.integrate(op.clone());
+        op
+    }
+
+    fn local_edit(&mut self, node_id: &str, kind: impl FnOnce(OpId) -> OpKind) -> Result<Vec<Op>, String> {
+        let target = self.component(node_id)?;
+        Ok(vec![self.local(kind(target))])
+    }
+
+    fn local_load(&mut self, json: &str) -> Result<Vec<Op>, String> {
+        let tree = serde_json::from_str(json).map_err(|e| format!("Failed to parse tree: {}", e))?;
+        let TreeInput::Component { component_type, props, children } = TreeInput::parse(&tree)? else {
+            return Err("the root must be a component".to_string());
+        };
+        let root = OpId::root();
+        let mut ops = vec![self.local(OpKind::SetType { target: root.clone(), component_type })];
+        self.fill(&root, props, children, &mut ops);
+        Ok(ops)
+    }
+
+    fn local_insert_child(&mut self, parent_id: &str, index: usize, child_json: &str) -> Result<Vec<Op>, String> {
+        let parent = self.component(parent_id)?;
+        let child = serde_json::from_str(child_json).map_err(|e| format!("Failed to parse child: {}", e))?;
+        let child = TreeInput::parse(&child)?;
+        let after = match index {
+            0 => None,
+            _ => Some(
+                self.visible_children(&parent)
+                    .nth(index - 1)
+                    .cloned()
+                    .ok_or_else(|| format!("index {} is out of range", index))?,
+            ),
+        };
+        let mut ops = Vec::new();
+        self.insert_tree(parent, after, child, &mut ops);
+        Ok(ops)
+    }
+
+    fn local_remove_child(&mut self, parent_id: &str, index: usize) -> Result<Vec<Op>, String> {
+        let parent = self.component(parent_id)?;
+        let target = self
+            .visible_children(&parent)
+            .nth(index)
+            .cloned()
+            .ok_or_else(|| format!("index {} is out of range", index))?;
+        Ok(vec![self.local(OpKind::Remove { target })])
+    }
+
+    fn insert_tree(&mut self, parent: OpId, after: Option<OpId>, tree: TreeInput, ops: &mut Vec<Op>) -> OpId {
+        let (kind, rest) = match tree {
+            TreeInput::Text(text) => {
+                (OpKind::Insert { parent, after, component_type: None, text: Some(text) }, None)
+            }
+            TreeInput::Component { component_type, props, children } => (
+                OpKind::Insert { parent, after, component_type: Some(component_type), text: None },
+                Some((props, children)),
+            ),
+        };
+        let op = self.local(kind);
+        let id = op.id.clone();
+        ops.push(op);
+        if let Some((props, children)) = rest {
+            self.fill(&id, props, children, ops);
+        }
+        id
+    }
+
+    /// Set props and append children of a component
+    fn fill(&mut self, id: &OpId, props: serde_json::Map<String, Value>, children: Vec<TreeInput>, ops: &mut Vec<Op>) {
+        for (key, value) in props {
+            ops.push(self.local(OpKind::SetProp { target: id.clone(), key, value }));
+        }
+        let mut after = self.visible_children(id).last().cloned();
+        for child in children {
+            after = Some(self.insert_tree(id.clone(), after, child, ops));
+        }
+    }
+
+    /// Queue remote operations and apply every one that is ready
+    fn receive(&mut self, ops: Vec<Op>) -> usize {
+        for op in ops {
+            let duplicate = op.id.seq <= self.seen(&op.id.replica) || self.pending.iter().any(|p| p.id == op.id);
+            if !duplicate && op.id.seq > 0 {
+                self.pending.push(op);
+            }
+        }
+        let mut applied = 0;
+        while let Some(index) = self.pending.iter().position(|op| self.is_ready(op)) {
+            let op = self.pending.remove(index);
+            if self.is_valid(&op) {
+                self.integrate(op);
+                applied += 1;
+            } else {
+                self.state_vector.insert(op.id.replica.clone(), op.id.seq);
+                self.log.push(op);
+                self.dropped += 1;
+            }
+        }
+        applied
+    }
+
+    /// Whether everything the operation depends on has been applied
+    fn is_ready(&self, op: &Op) -> bool {
+        op.id.seq == self.seen(&op.id.replica) + 1
+            && match &op.kind {
+                OpKind::Insert { parent, after, .. } => {
+                    self.nodes.contains_key(parent) && after.as_ref().is_none_or(|after| self.nodes.contains_key(after))
+                }
+                OpKind::Remove { target }
+                | OpKind::SetType { target, .. }
+                | OpKind::SetProp { target, .. }
+                | OpKind::RemoveProp { target, .. } => self.nodes.contains_key(target),
+            }
+    }
+
+    /// Whether a ready operation fits the tree: inserts go into a component
+    /// after one of its children, and only components change type or props
+    fn is_valid(&self, op: &Op) -> bool {
+        let is_component = |id: &OpId| self.nodes.get(id).is_some_and(|node| node.text.is_none());
+        match &op.kind {
+            OpKind::Insert { parent, after, .. } => {
+                is_component(parent)
+                    && after.as_ref().is_none_or(|after| self.nodes[parent].children.contains(after))
+            }
+            OpKind::Remove { .. } => true,
+            OpKind::SetType { target, .. } | OpKind::SetProp { target, .. } | OpKind::RemoveProp { target, .. } => {
+                is_component(target)
+            }
+        }
+    }
+
+    fn integrate(&mut self, op: Op) {
+        self.state_vector.insert(op.id.replica.clone(), op.id.seq);
+        self.lamport = self.lamport.max(op.lamport);
+        let stamp = Stamp { lamport: op.lamport, replica: op.id.replica.clone() };
+        match &op.kind {
+            OpKind::Insert { parent, after, component_type, text } => {
+                let position = self.insert_position(parent, after.as_ref(), &stamp);
+                let node = Node::new(text.clone(), component_type.clone().unwrap_or_default(), stamp);
+                self.nodes.insert(op.id.clone(), node);
+                if let Some(parent) = self.nodes.get_mut(parent) {
+                    parent.children.insert(position, op.id.clone());
+                }
+            }
+            OpKind::Remove { target } => {
+                if let Some(node) = self.nodes.get_mut(target).filter(|_| target.seq > 0) {
+                    node.removed = true;
+                }
+            }
+            OpKind::SetType { target, component_type } => {
+                if let Some(node) = self.nodes.get_mut(target) {
+                    node.component_type.set(component_type.clone(), &stamp);
+                }
+            }
+            OpKind::SetProp { target, key, value } => self.set_prop_register(target, key, Some(value.clone()), &stamp),
+            OpKind::RemoveProp { target, key } => self.set_prop_register(target, key, None, &stamp),
+        }
+        self.log.push(op);
+    }
+
+    fn set_prop_register(&mut self, target: &OpId, key: &str, value: Option<Value>, stamp: &Stamp) {
+        if let Some(node) = self.nodes.get_mut(target) {
+            node.props
+                .entry(key.to_string())
+                .or_insert_with(|| Register { value: None, stamp: Stamp::default() })
+                .set(value, stamp);
+        }
+    }
+
+    /// RGA placement: right after `after`, but behind siblings inserted
+    /// there with a later stamp (and everything inserted after those)
+    fn insert_position(&self, parent: &OpId, after: Option<&OpId>, stamp: &Stamp) -> usize {
+        let Some(parent) = self.nodes.get(parent) else {
+            return 0;
+        };
+        let mut position = after
+            .and_then(|after| parent.children.iter().position(|id| id == after))
+            .map_or(0, |index| index + 1);
+        while parent.children.get(position).and_then(|id| self.nodes.get(id)).is_some_and(|node| node.stamp > *stamp) {
+            position += 1;
+        }
+        position
+    }
+
+    fn missing_ops(&self, state_vector: &BTreeMap<String, u64>) -> Vec<&Op> {
+        self.log
+            .iter()
+            .filter(|op| op.id.seq > state_vector.get(&op.id.replica).copied().unwrap_or(0))
+            .collect()
+    }
+
+    fn component_tree(&self, id: &OpId) -> SerializedComponent {
+        let node = &self.nodes[id];
+        let props: serde_json::Map<String, Value> = node
+            .props
+            .iter()
+            .filter_map(|(key, register)| register.value.clone().map(|value| (key.clone(), value)))
+            .collect();
+        let mut component = SerializedComponent::new(node.component_type.value.clone(), Some(Value::Object(props).to_string()));
+        for child in self.visible_children(id) {
+            match &self.nodes[child].text {
+                Some(text) => component.add_text_child(text.clone()),
+                None => component.add_component_child(self.component_tree(child)),
+            }
+        }
+        component
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn replica(id: &str) -> CollaborativeTree {
+        CollaborativeTree::with_replica(id.to_string())
+    }
+
+    fn page() -> &'static str {
+        r#"{"type": "Page", "props": "{\"title\": \"Home\"}", "children": ["intro", {"type": "List", "props": {"dense": true}, "children": [{"type": "Item"}]}]}"#
+    }
+
+    /// Two replicas that both hold `page()`
+    fn pair() -> (CollaborativeTree, CollaborativeTree) {
+        let mut alice = replica("alice");
+        alice.load_json(page()).unwrap();
+        let mut bob = replica("bob");
+        bob.apply_remote(&alice.sync_message("{}").unwrap()).unwrap();
+        assert_eq!(bob.to_json(), alice.to_json());
+        (alice, bob)
+    }
+
+    #[test]
+    fn test_concurrent_edits_converge() {
+        let (mut alice, mut bob) = pair();
+        let list = alice.child_id("root", 1).unwrap();
+        let item = alice.child_id(&list, 0).unwrap();
+
+        let from_alice = [
+            alice.insert_child("root", 1, r#"{"type": "A"}"#).unwrap(),
+            alice.set_prop("root", "title", r#""Alice""#).unwrap(),
+            alice.set_prop(&item, "label", r#""first""#).unwrap(),
+        ];
+        let from_bob = [
+            bob.insert_child("root", 1, r#"{"type": "B", "children": ["b"]}"#).unwrap(),
+            bob.set_prop("root", "title", r#""Bob""#).unwrap(),
+            bob.remove_child(&list, 0).unwrap(),
+        ];
+
+        // Delivered in opposite orders
+        for ops in from_bob.iter() {
+            alice.apply_remote(ops).unwrap();
+        }
+        for ops in from_alice.iter().rev() {
+            bob.apply_remote(ops).unwrap();
+        }
+        assert_eq!(alice.pending_count(), 0);
+        assert_eq!(bob.pending_count(), 0);
+        assert_eq!(alice.to_json(), bob.to_json());
+
+        // Bob's title is the later write; of the two inserts with equal
+        // timestamps, the larger replica id goes first
+        let tree: Value = serde_json::from_str(&alice.to_json()).unwrap();
+        assert_eq!(tree["props"], r#"{"title":"Bob"}"#);
+        let types: Vec<&Value> = tree["children"].as_array().unwrap().iter().map(|c| &c["type"]).collect();
+        assert_eq!(types, vec![&Value::Null, &json!("B"), &json!("A"), &json!("List")]);
+        // The edit inside the removed item is kept in its tombstone only
+        assert_eq!(tree["children"][3], json!({"type": "List", "props": r#"{"dense":true}"#, "children": []}));
+    }
+
+    #[test]
+    fn test_out_of_order_delivery() {
+        let mut alice = replica("alice");
+        let load = alice.load_json(page()).unwrap();
+        let insert = alice.insert_child("root", 2, r#""outro""#).unwrap();
+        let list = alice.child_id("root", 1).unwrap();
+        let prop = alice.set_prop(&list, "dense", "false").unwrap();
+        let unset = alice.remove_prop("root", "title").unwrap();
+
+        let mut carol = replica("carol");
+        assert_eq!(carol.apply_remote(&prop).unwrap(), 0);
+        assert_eq!(carol.apply_remote(&insert).unwrap(), 0);
+        assert_eq!(carol.pending_count(), 2);
+        let applied = carol.apply_remote(&load).unwrap();
+        assert_eq!(carol.pending_count(), 0);
+        assert_eq!(applied, alice.log.len() - 1);
+        assert_eq!(carol.apply_remote(&load).unwrap(), 0);
+        assert_eq!(carol.apply_remote(&unset).unwrap(), 1);
+        assert_eq!(carol.to_json(), alice.to_json());
+        assert_eq!(
+            serde_json::from_str::<Value>(&carol.to_json()).unwrap(),
+            json!({"type": "Page", "props": "{}", "children": [
+                "intro",
+                {"type": "List", "props": r#"{"dense":false}"#, "children": [{"type": "Item", "props": "{}", "children": []}]},
+                "outro"
+            ]})
+        );
+        // The output is a SerializedComponent and loads into a new replica
+        assert_eq!(SerializedComponent::from_json(&carol.to_json()).unwrap().to_json(), carol.to_json());
+        let mut dave = replica("dave");
+        dave.load_json(&carol.to_json()).unwrap();
+        assert_eq!(dave.to_json(), carol.to_json());
+
+        assert!(carol.local_insert_child("root", 4, r#"{"type": "X"}"#).is_err());
+        assert!(carol.local_insert_child("root", 0, r#"{"props": {}}"#).is_err());
+        assert!(carol.local_edit("9@nobody", |target| OpKind::Remove { target }).is_err());
+        let text = carol.child_id("root", 0).unwrap();
+        assert!(carol.local_insert_child(&text, 0, r#""x""#).is_err());
+        assert!("0@alice".parse::<OpId>().is_err());
+        assert_eq!("2@a@b".parse::<OpId>().unwrap().replica, "a@b");
+    }
+
+    #[test]
+    fn test_state_vector_sync() {
+        let (mut alice, mut bob) = pair();
+        alice.set_type("root", "Landing").unwrap();
+        alice.insert_child("root", 0, r#"{"type": "Banner"}"#).unwrap();
+        bob.remove_child("root", 0).unwrap();
+        assert_eq!(alice.state_vector_json(), format!(r#"{{"alice":{}}}"#, alice.log.len()));
+
+        let for_bob: Vec<Value> = serde_json::from_str(&alice.sync_message(&bob.state_vector_json()).unwrap()).unwrap();
+        assert_eq!(for_bob.len(), 2);
+        assert_eq!(for_bob[0]["op"], "set_type");
+        let for_alice = bob.sync_message(&alice.state_vector_json()).unwrap();
+        bob.apply_remote(&serde_json::to_string(&for_bob).unwrap()).unwrap();
+        alice.apply_remote(&for_alice).unwrap();
+
+        assert_eq!(alice.state_vector_json(), bob.state_vector_json());
+        assert_eq!(alice.to_json(), bob.to_json());
+        assert_eq!(alice.sync_message(&bob.state_vector_json()).unwrap(), "[]");
+        let tree: Value = serde_json::from_str(&bob.to_json()).unwrap();
+        assert_eq!(tree["type"], "Landing");
+        assert_eq!(tree["children"][0]["type"], "Banner");
+        assert_eq!(tree["children"][1]["type"], "List");
+    }
+
+    #[test]
+    fn test_invalid_ops_dropped() {
+        let (alice, mut bob) = pair();
+        let text = alice.child_id("root", 0).unwrap();
+        let item = alice.child_id(&alice.child_id("root", 1).unwrap(), 0).unwrap();
+        let op = |seq: u64, kind: Value| {
+            let mut op = json!({"id": format!("{}@mallory", seq), "lamport": 100 + seq});
+            op.as_object_mut().unwrap().extend(kind.as_object().unwrap().clone());
+            op
+        };
+        let ops = json!([
+            op(1, json!({"op": "insert", "parent": text, "after": null, "type": "X"})),
+            op(2, json!({"op": "insert", "parent": "root", "after": item, "type": "X"})),
+            op(3, json!({"op": "set_prop", "target": text, "key": "x", "value": 1})),
+            op(4, json!({"op": "set_type", "target": text, "type": "X"})),
+            op(5, json!({"op": "remove_prop", "target": text, "key": "x"})),
+            op(6, json!({"op": "set_prop", "target": "root", "key": "title", "value": "Valid"})),
+        ]);
+
+        let before = bob.to_json();
+        // Delivered last first: the valid op waits for the dropped ones
+        for op in ops.as_array().unwrap().iter().rev() {
+            bob.apply_remote(&json!([op]).to_string()).unwrap();
+        }
+        assert_eq!(bob.dropped_count(), 5);
+        assert_eq!(bob.pending_count(), 0);
+        assert_eq!(bob.state_vector_json(), format!(r#"{{"alice":{},"mallory":6}}"#, alice.log.len()));
+        let tree: Value = serde_json::from_str(&bob.to_json()).unwrap();
+        assert_eq!(tree["props"], r#"{"title":"Valid"}"#);
+        assert_eq!(tree["children"], serde_json::from_str::<Value>(&before).unwrap()["children"]);
+
+        // Relayed, so a peer that syncs from bob drops them too
+        let mut carol = replica("carol");
+        assert_eq!(carol.apply_remote(&bob.sync_message("{}").unwrap()).unwrap(), alice.log.len() + 1);
+        assert_eq!(carol.dropped_count(), 5);
+        assert_eq!(carol.to_json(), bob.to_json());
+    }
+}
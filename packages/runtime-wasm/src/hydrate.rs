@@ -2,10 +2,34 @@
 //!
 //! Handles serialization and deserialization of React component trees
 //! for storage in Aeon sessions and rendering on client/server.
+//!
+//! `diff_trees` understands component nodes (objects with a `type`): a
+//! change of `type` or `key` replaces the whole subtree, and children are
+//! matched by their `props.key` (unkeyed children by position among the
+//! unkeyed ones), so reordering or inserting into a list produces `move`,
+//! `insert` and `remove` operations instead of an update per index. Moves
+//! are minimal: children on a longest increasing subsequence stay put.
+//!
+//! Diffs apply in order. Array indices in a diff refer to the array as left
+//! by the diffs before it, and `apply_patch(old, diff_trees(old, new))`
+//! always yields `new`.
+//!
+//! `merge_trees` merges two concurrent edits of the same tree against their
+//! common base. Edits to different props or children combine; keyed children
+//! are matched by key as in `diff_trees`, unkeyed ones by aligning each side
+//! with the base, so inserting a child does not misalign its siblings.
+//! Conflicting edits are resolved by the `MergePolicy` and reported against
+//! the merged tree.
+//!
+//! Diff paths are dotted (`children.0.props.text`); a `.` or `\` inside a
+//! key is escaped with `\`. The same diffs can be exchanged as RFC 6902 JSON
+//! Patch (`diff_trees_json_patch`, `tree_diff_to_json_patch`,
+//! `json_patch_to_tree_diff`), and `apply_patch` accepts either format.
 
 use wasm_bindgen::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use crate::json_patch::{self, Operation};
 
 /// Component registry - maps component names to their render functions
 #[wasm_bindgen]
@@ -61,12 +85,17 @@ impl Default for ComponentRegistry {
 pub struct TreeDiff {
     /// Path to the changed node (e.g., "children.0.props.text")
     path: String,
-    /// Type of change: "add", "remove", "update"
+    /// Type of change: "add", "remove", "update", "replace" (a different
+    /// component), "insert" (into an array) or "move"
     change_type: String,
     /// Old value (JSON)
     old_value: Option<String>,
     /// New value (JSON)
     new_value: Option<String>,
+    /// For "move": where the node is taken from; `path` is where it is
+    /// inserted after that removal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
 }
 
 #[wasm_bindgen]
@@ -83,6 +112,7 @@ impl TreeDiff {
             change_type,
             old_value,
             new_value,
+            from: None,
         }
     }
 
@@ -110,11 +140,54 @@ impl TreeDiff {
         self.new_value.clone()
     }
 
+    /// Get the source path of a move
+    #[wasm_bindgen(getter)]
+    pub fn from(&self) -> Option<String> {
+        self.from.clone()
+    }
+
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
     }
 }
 
+impl TreeDiff {
+    /// The equivalent JSON Patch operation; `None` for unknown change types
+    /// or values that are not valid JSON
+    fn to_operation(&self) -> Option<Operation> {
+        let path = split_path(&self.path);
+        let value = || self.new_value.as_deref().and_then(|value| serde_json::from_str(value).ok());
+        let from = || self.from.as_deref().map(split_path);
+        Some(match self.change_type.as_str() {
+            "add" | "insert" => Operation::Add { path, value: value()? },
+            "update" | "replace" => Operation::Replace { path, value: value()? },
+            "remove" => Operation::Remove { path },
+            "move" => Operation::Move { from: from()?, path },
+            "copy" => Operation::Copy { from: from()?, path },
+            "test" => Operation::Test { path, value: value()? },
+            _ => return None,
+        })
+    }
+
+    fn from_operation(operation: &Operation) -> Self {
+        let diff = |path: &[String], change_type: &str, value: Option<&serde_json::Value>| {
+            TreeDiff::new(join_path(path), change_type.to_string(), None, value.map(|v| v.to_string()))
+        };
+        let with_from = |mut diff: TreeDiff, from: &[String]| {
+            diff.from = Some(join_path(from));
+            diff
+        };
+        match operation {
+            Operation::Add { path, value } => diff(path, "insert", Some(value)),
+            Operation::Remove { path } => diff(path, "remove", None),
+            Operation::Replace { path, value } => diff(path, "replace", Some(value)),
+            Operation::Move { from, path } => with_from(diff(path, "move", None), from),
+            Operation::Copy { from, path } => with_from(diff(path, "copy", None), from),
+            Operation::Test { path, value } => diff(path, "test", Some(value)),
+        }
+    }
+}
+
 /// Compute diff between two component trees
 #[wasm_bindgen]
 pub fn diff_trees(old_json: &str, new_json: &str) -> String {
@@ -122,6 +195,55 @@ pub fn diff_trees(old_json: &str, new_json: &str) -> String {
     serde_json::to_string(&diffs).unwrap_or_else(|_| "[]".to_string())
 }
 
+/// Compute the diff between two component trees as RFC 6902 JSON Patch
+#[wasm_bindgen]
+pub fn diff_trees_json_patch(old_json: &str, new_json: &str) -> String {
+    let operations: Vec<serde_json::Value> = compute_diff(old_json, new_json, "")
+        .iter()
+        .filter_map(TreeDiff::to_operation)
+        .map(|operation| operation.to_json())
+        .collect();
+    serde_json::to_string(&operations).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Convert a `TreeDiff` array to JSON Patch (unknown change types are dropped)
+#[wasm_bindgen]
+pub fn tree_diff_to_json_patch(diffs_json: &str) -> String {
+    let diffs: Vec<TreeDiff> = serde_json::from_str(diffs_json).unwrap_or_default();
+    let operations: Vec<serde_json::Value> = diffs
+        .iter()
+        .filter_map(TreeDiff::to_operation)
+        .map(|operation| operation.to_json())
+        .collect();
+    serde_json::to_string(&operations).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Convert a JSON Patch to a `TreeDiff` array
+#[wasm_bindgen]
+pub fn json_patch_to_tree_diff(patch_json: &str) -> Result<String, JsValue> {
+    let operations = parse_json_patch(patch_json).map_err(|e| JsValue::from_str(&e))?;
+    let diffs: Vec<TreeDiff> = operations.iter().map(TreeDiff::from_operation).collect();
+    Ok(serde_json::to_string(&diffs).unwrap_or_else(|_| "[]".to_string()))
+}
+
+/// Apply an RFC 6902 JSON Patch, failing (with no changes) if any operation
+/// fails
+#[wasm_bindgen]
+pub fn apply_json_patch(tree_json: &str, patch_json: &str) -> Result<String, JsValue> {
+    let mut tree: serde_json::Value = serde_json::from_str(tree_json)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse tree: {}", e)))?;
+    parse_json_patch(patch_json)
+        .and_then(|operations| json_patch::apply(&mut tree, &operations))
+        .map_err(|e| JsValue::from_str(&e))?;
+    Ok(serde_json::to_string(&tree).unwrap_or_else(|_| "{}".to_string()))
+}
+
+fn parse_json_patch(patch_json: &str) -> Result<Vec<Operation>, String> {
+    let patch: serde_json::Value =
+        serde_json::from_str(patch_json).map_err(|e| format!("Failed to parse patch: {}", e))?;
+    json_patch::parse_patch(&patch)
+}
+
 /// Internal diff computation
 fn compute_diff(old_json: &str, new_json: &str, path: &str) -> Vec<TreeDiff> {
     let mut diffs = Vec::new();
@@ -136,9 +258,11 @@ fn compute_diff(old_json: &str, new_json: &str, path: &str) -> Vec<TreeDiff> {
 fn diff_values(old: &serde_json::Value, new: &serde_json::Value, path: &str, diffs: &mut Vec<TreeDiff>) {
     use serde_json::Value;
 
+    // Below the root, null is a value like any other; missing object keys
+    // and array elements are handled by `diff_objects` and the array arm
     match (old, new) {
         (Value::Null, Value::Null) => {}
-        (Value::Null, _) => {
+        (Value::Null, _) if path.is_empty() => {
             diffs.push(TreeDiff::new(
                 path.to_string(),
                 "add".to_string(),
@@ -146,7 +270,7 @@ fn diff_values(old: &serde_json::Value, new: &serde_json::Value, path: &str, dif
                 Some(new.to_string()),
             ));
         }
-        (_, Value::Null) => {
+        (_, Value::Null) if path.is_empty() => {
             diffs.push(TreeDiff::new(
                 path.to_string(),
                 "remove".to_string(),
@@ -154,53 +278,31 @@ fn diff_values(old: &serde_json::Value, new: &serde_json::Value, path: &str, dif
                 None,
             ));
         }
-        (Value::Object(old_map), Value::Object(new_map)) => {
-            // Check for removed keys
-            for key in old_map.keys() {
-                if !new_map.contains_key(key) {
-                    let child_path = if path.is_empty() {
-                        key.clone()
-                    } else {
-                        format!("{}.{}", path, key)
-                    };
-                    diffs.push(TreeDiff::new(
-                        child_path,
-                        "remove".to_string(),
-                        Some(old_map[key].to_string()),
-                        None,
-                    ));
-                }
-            }
-            // Check for added/changed keys
-            for (key, new_val) in new_map {
-                let child_path = if path.is_empty() {
-                    key.clone()
-                } else {
-                    format!("{}.{}", path, key)
-                };
-                if let Some(old_val) = old_map.get(key) {
-                    diff_values(old_val, new_val, &child_path, diffs);
-                } else {
-                    diffs.push(TreeDiff::new(
-                        child_path,
-                        "add".to_string(),
-                        None,
-                        Some(new_val.to_string()),
-                    ));
-                }
-            }
+        (Value::Object(_), Value::Object(_)) if is_component(old) && is_component(new) => {
+            diff_components(old, new, path, diffs);
         }
+        (Value::Object(old_map), Value::Object(new_map)) => diff_objects(old_map, new_map, path, diffs),
         (Value::Array(old_arr), Value::Array(new_arr)) => {
-            let max_len = old_arr.len().max(new_arr.len());
-            for i in 0..max_len {
-                let child_path = if path.is_empty() {
-                    format!("{}", i)
-                } else {
-                    format!("{}.{}", path, i)
-                };
-                let old_item = old_arr.get(i).unwrap_or(&Value::Null);
-                let new_item = new_arr.get(i).unwrap_or(&Value::Null);
-                diff_values(old_item, new_item, &child_path, diffs);
+            for (i, (old_item, new_item)) in old_arr.iter().zip(new_arr).enumerate() {
+                diff_values(old_item, new_item, &child_path(path, &i.to_string()), diffs);
+            }
+            // Appends in ascending order, removes from the end backwards, so
+            // every index is valid when the diff is applied
+            for (i, new_item) in new_arr.iter().enumerate().skip(old_arr.len()) {
+                diffs.push(TreeDiff::new(
+                    child_path(path, &i.to_string()),
+                    "add".to_string(),
+                    None,
+                    Some(new_item.to_string()),
+                ));
+            }
+            for (i, old_item) in old_arr.iter().enumerate().skip(new_arr.len()).rev() {
+                diffs.push(TreeDiff::new(
+                    child_path(path, &i.to_string()),
+                    "remove".to_string(),
+                    Some(old_item.to_string()),
+                    None,
+                ));
             }
         }
         _ => {
@@ -216,101 +318,719 @@ fn diff_values(old: &serde_json::Value, new: &serde_json::Value, path: &str, dif
     }
 }
 
+fn diff_objects(
+    old_map: &serde_json::Map<String, serde_json::Value>,
+    new_map: &serde_json::Map<String, serde_json::Value>,
+    path: &str,
+    diffs: &mut Vec<TreeDiff>,
+) {
+    // Check for removed keys
+    for key in old_map.keys() {
+        if !new_map.contains_key(key) {
+            diffs.push(TreeDiff::new(
+                child_path(path, key),
+                "remove".to_string(),
+                Some(old_map[key].to_string()),
+                None,
+            ));
+        }
+    }
+    // Check for added/changed keys
+    for (key, new_val) in new_map {
+        let child_path = child_path(path, key);
+        if let Some(old_val) = old_map.get(key) {
+            diff_values(old_val, new_val, &child_path, diffs);
+        } else {
+            diffs.push(TreeDiff::new(
+                child_path,
+                "add".to_string(),
+                None,
+                Some(new_val.to_string()),
+            ));
+        }
+    }
+}
+
+/// Join a path and a key or index with `.`, escaping the key. An empty
+/// first key is written as a leading `.` so it is not the root.
+fn child_path(path: &str, key: &str) -> String {
+    let key = key.replace('\\', "\\\\").replace('.', "\\.");
+    if path.is_empty() {
+        if key.is_empty() { ".".to_string() } else { key }
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn join_path(keys: &[String]) -> String {
+    keys.iter().fold(String::new(), |path, key| child_path(&path, key))
+}
+
+/// Split a dotted path into unescaped keys (`""` is the root)
+fn split_path(path: &str) -> Vec<String> {
+    if path.is_empty() {
+        return Vec::new();
+    }
+    let mut keys = Vec::new();
+    let mut key = String::new();
+    let mut chars = path.strip_prefix('.').unwrap_or(path).chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => key.extend(chars.next()),
+            '.' => keys.push(std::mem::take(&mut key)),
+            _ => key.push(c),
+        }
+    }
+    keys.push(key);
+    keys
+}
+
+/// Whether a value is a serialized component (`{ type, props?, children? }`)
+fn is_component(value: &serde_json::Value) -> bool {
+    value.get("type").is_some_and(|t| t.is_string())
+}
+
+/// `props.key` of a component, as a string
+fn component_key(value: &serde_json::Value) -> Option<String> {
+    match value.get("props")?.get("key")? {
+        serde_json::Value::String(key) => Some(key.clone()),
+        serde_json::Value::Number(key) => Some(key.to_string()),
+        _ => None,
+    }
+}
+
+fn diff_components(old: &serde_json::Value, new: &serde_json::Value, path: &str, diffs: &mut Vec<TreeDiff>) {
+    use serde_json::Value;
+
+    if old.get("type") != new.get("type") || component_key(old) != component_key(new) {
+        diffs.push(TreeDiff::new(
+            path.to_string(),
+            "replace".to_string(),
+            Some(old.to_string()),
+            Some(new.to_string()),
+        ));
+        return;
+    }
+    let (Value::Object(old_map), Value::Object(new_map)) = (old, new) else {
+        return;
+    };
+    let (Some(Value::Array(old_children)), Some(Value::Array(new_children))) =
+        (old_map.get("children"), new_map.get("children"))
+    else {
+        diff_objects(old_map, new_map, path, diffs);
+        return;
+    };
+    let without_children = |map: &serde_json::Map<String, Value>| {
+        map.iter().filter(|(k, _)| *k != "children").map(|(k, v)| (k.clone(), v.clone())).collect()
+    };
+    diff_objects(&without_children(old_map), &without_children(new_map), path, diffs);
+    diff_children(old_children, new_children, &child_path(path, "children"), diffs);
+}
+
+/// How a child is matched between the old and new children
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ChildIdentity {
+    Keyed(String),
+    /// Position among the unkeyed children (and repeated keys)
+    Unkeyed(usize),
+}
+
+fn child_identities(children: &[serde_json::Value]) -> Vec<ChildIdentity> {
+    let mut seen = std::collections::HashSet::new();
+    let mut unkeyed = 0;
+    children
+        .iter()
+        .map(|child| match component_key(child).filter(|key| seen.insert(key.clone())) {
+            Some(key) => ChildIdentity::Keyed(key),
+            None => {
+                unkeyed += 1;
+                ChildIdentity::Unkeyed(unkeyed - 1)
+            }
+        })
+        .collect()
+}
+
+/// Structural operations (removes, then moves and inserts right to left),
+/// followed by content diffs of the matched children at their new indices
+fn diff_children(old: &[serde_json::Value], new: &[serde_json::Value], path: &str, diffs: &mut Vec<TreeDiff>) {
+    let old_ids = child_identities(old);
+    let new_ids = child_identities(new);
+    let new_index: HashMap<&ChildIdentity, usize> = new_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+    let old_index: HashMap<&ChildIdentity, usize> = old_ids.iter().enumerate().map(|(i, id)| (id, i)).collect();
+
+    for (i, id) in old_ids.iter().enumerate().rev() {
+        if !new_index.contains_key(id) {
+            diffs.push(TreeDiff::new(
+                child_path(path, &i.to_string()),
+                "remove".to_string(),
+                Some(old[i].to_string()),
+                None,
+            ));
+        }
+    }
+
+    // Kept children in old order; those on the longest run already in new
+    // order stay, the rest move
+    let mut working: Vec<&ChildIdentity> = old_ids.iter().filter(|id| new_index.contains_key(id)).collect();
+    let positions: Vec<usize> = working.iter().map(|id| new_index[id]).collect();
+    let stable: std::collections::HashSet<usize> =
+        longest_increasing_subsequence(&positions).into_iter().map(|i| positions[i]).collect();
+
+    for (i, id) in new_ids.iter().enumerate().rev() {
+        if stable.contains(&i) {
+            continue;
+        }
+        let anchor = new_ids
+            .get(i + 1)
+            .and_then(|next| working.iter().position(|w| *w == next))
+            .unwrap_or(working.len());
+        match working.iter().position(|w| *w == id) {
+            Some(from) => {
+                working.remove(from);
+                let to = if from < anchor { anchor - 1 } else { anchor };
+                working.insert(to, id);
+                if from != to {
+                    let mut diff = TreeDiff::new(child_path(path, &to.to_string()), "move".to_string(), None, None);
+                    diff.from = Some(child_path(path, &from.to_string()));
+                    diffs.push(diff);
+                }
+            }
+            None => {
+                working.insert(anchor, id);
+                diffs.push(TreeDiff::new(
+                    child_path(path, &anchor.to_string()),
+                    "insert".to_string(),
+                    None,
+                    Some(new[i].to_string()),
+                ));
+            }
+        }
+    }
+
+    for (i, id) in new_ids.iter().enumerate() {
+        if let Some(&old_i) = old_index.get(id) {
+            diff_values(&old[old_i], &new[i], &child_path(path, &i.to_string()), diffs);
+        }
+    }
+}
+
+/// Indices into `values` of one longest strictly increasing subsequence
+fn longest_increasing_subsequence(values: &[usize]) -> Vec<usize> {
+    // tails[k]: index of the smallest tail of an increasing run of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![usize::MAX; values.len()];
+    for (i, &value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&t| values[t] < value);
+        if k > 0 {
+            previous[i] = tails[k - 1];
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+    let mut sequence = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        sequence.push(i);
+        current = (previous[i] != usize::MAX).then_some(previous[i]);
+    }
+    sequence.reverse();
+    sequence
+}
+
 /// Apply a patch to a component tree
+///
+/// Accepts a `TreeDiff` array or an RFC 6902 JSON Patch. Either format is
+/// applied atomically: if it fails to parse or any operation fails
+/// (including a failed "test"), the result is an error and no changes are
+/// kept.
 #[wasm_bindgen]
-pub fn apply_patch(tree_json: &str, patch_json: &str) -> String {
-    let mut tree: serde_json::Value = serde_json::from_str(tree_json)
-        .unwrap_or(serde_json::Value::Null);
+pub fn apply_patch(tree_json: &str, patch_json: &str) -> Result<String, JsValue> {
+    patch_tree(tree_json, patch_json).map_err(|e| JsValue::from_str(&e))
+}
 
-    let patches: Vec<TreeDiff> = serde_json::from_str(patch_json)
-        .unwrap_or_default();
+fn patch_tree(tree_json: &str, patch_json: &str) -> Result<String, String> {
+    let mut tree: serde_json::Value =
+        serde_json::from_str(tree_json).map_err(|e| format!("Failed to parse tree: {}", e))?;
+    let patch: serde_json::Value =
+        serde_json::from_str(patch_json).map_err(|e| format!("Failed to parse patch: {}", e))?;
 
-    for patch in patches {
-        apply_single_patch(&mut tree, &patch);
+    if json_patch::is_json_patch(&patch) {
+        let operations = json_patch::parse_patch(&patch)?;
+        json_patch::apply(&mut tree, &operations)?;
+    } else {
+        let patches: Vec<TreeDiff> =
+            serde_json::from_value(patch).map_err(|e| format!("Failed to parse patch: {}", e))?;
+        // Work on a copy so a failure part-way leaves the tree untouched
+        let mut patched = tree.clone();
+        for patch in &patches {
+            apply_single_patch(&mut patched, patch)
+                .map_err(|e| format!("{} at {} failed: {}", patch.change_type, patch.path, e))?;
+        }
+        tree = patched;
     }
 
-    serde_json::to_string(&tree).unwrap_or_else(|_| "{}".to_string())
+    Ok(serde_json::to_string(&tree).unwrap_or_else(|_| "{}".to_string()))
 }
 
-fn apply_single_patch(tree: &mut serde_json::Value, patch: &TreeDiff) {
-    let path_parts: Vec<&str> = patch.path.split('.').filter(|s| !s.is_empty()).collect();
+/// Apply one diff
+fn apply_single_patch(tree: &mut serde_json::Value, patch: &TreeDiff) -> Result<(), String> {
+    let path_parts = split_path(&patch.path);
+    let new_value = || -> Result<serde_json::Value, String> {
+        let value = patch.new_value.as_deref().ok_or("missing new_value")?;
+        serde_json::from_str(value).map_err(|e| format!("invalid new_value: {}", e))
+    };
+    let from = || patch.from.as_deref().map(split_path).ok_or("missing from");
 
-    if path_parts.is_empty() {
-        // Root-level change
-        match patch.change_type.as_str() {
-            "add" | "update" => {
-                if let Some(new_val) = &patch.new_value {
-                    if let Ok(val) = serde_json::from_str(new_val) {
-                        *tree = val;
-                    }
-                }
+    match patch.change_type.as_str() {
+        "update" | "replace" => set_value(tree, &path_parts, new_value()?),
+        "add" | "insert" => json_patch::add(tree, &path_parts, new_value()?),
+        "remove" => json_patch::remove(tree, &path_parts).map(drop),
+        "move" => {
+            let value = json_patch::remove(tree, &from()?)?;
+            json_patch::add(tree, &path_parts, value)
+        }
+        "copy" => {
+            let value = json_patch::get(tree, &from()?)?.clone();
+            json_patch::add(tree, &path_parts, value)
+        }
+        "test" => {
+            if json_patch::get(tree, &path_parts)? == &new_value()? {
+                Ok(())
+            } else {
+                Err("test failed".to_string())
             }
-            "remove" => {
-                *tree = serde_json::Value::Null;
+        }
+        other => Err(format!("unknown change type \"{}\"", other)),
+    }
+}
+
+/// Set an object key or an existing array element
+fn set_value(tree: &mut serde_json::Value, path_parts: &[String], value: serde_json::Value) -> Result<(), String> {
+    let Some((last, parent)) = path_parts.split_last() else {
+        *tree = value;
+        return Ok(());
+    };
+    match json_patch::get_mut(tree, parent)? {
+        serde_json::Value::Array(arr) => {
+            let element = last.parse::<usize>().ok().and_then(|idx| arr.get_mut(idx));
+            *element.ok_or_else(|| format!("index {} is out of bounds", last))? = value;
+        }
+        serde_json::Value::Object(obj) => {
+            obj.insert(last.to_string(), value);
+        }
+        _ => return Err("parent is not an object or array".to_string()),
+    }
+    Ok(())
+}
+
+/// How `merge_trees` resolves conflicting edits
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergePolicy {
+    /// Keep our edit
+    Ours,
+    /// Keep their edit
+    Theirs,
+    /// Keep the edit of the side with the later timestamp (ours on a tie)
+    LastWriterWins,
+}
+
+/// One side of a merge
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+/// Why two edits could not be combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeConflictKind {
+    /// Both sides changed the same value differently
+    BothChanged,
+    /// Both sides added the same key or keyed child with different values
+    BothAdded,
+    /// One side removed a subtree the other side edited
+    EditRemove,
+    /// Both sides reordered the same children differently
+    Reordered,
+}
+
+/// A conflict and how it was resolved
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MergeConflict {
+    /// Dotted path of the value in the merged tree, as in `TreeDiff`; a
+    /// child the merge removed is indexed where it would have been
+    path: String,
+    kind: MergeConflictKind,
+    /// Values on each side; `None` where absent
+    base: Option<serde_json::Value>,
+    ours: Option<serde_json::Value>,
+    theirs: Option<serde_json::Value>,
+    /// The side whose value was kept
+    resolution: MergeSide,
+}
+
+/// Options for `merge_trees`
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct MergeOptions {
+    policy: MergePolicy,
+    ours_timestamp: f64,
+    theirs_timestamp: f64,
+}
+
+#[wasm_bindgen]
+impl MergeOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new(policy: MergePolicy) -> Self {
+        Self {
+            policy,
+            ours_timestamp: 0.0,
+            theirs_timestamp: 0.0,
+        }
+    }
+
+    /// When each side was last edited (e.g. ms since the epoch), for
+    /// `LastWriterWins`
+    pub fn set_timestamps(&mut self, ours: f64, theirs: f64) {
+        self.ours_timestamp = ours;
+        self.theirs_timestamp = theirs;
+    }
+}
+
+impl MergeOptions {
+    fn winner(&self) -> MergeSide {
+        match self.policy {
+            MergePolicy::Ours => MergeSide::Ours,
+            MergePolicy::Theirs => MergeSide::Theirs,
+            MergePolicy::LastWriterWins if self.theirs_timestamp > self.ours_timestamp => MergeSide::Theirs,
+            MergePolicy::LastWriterWins => MergeSide::Ours,
+        }
+    }
+}
+
+/// Merged tree and the conflicts resolved along the way
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct MergeResult {
+    merged: serde_json::Value,
+    conflicts: Vec<MergeConflict>,
+}
+
+#[wasm_bindgen]
+impl MergeResult {
+    #[wasm_bindgen(getter)]
+    pub fn merged_json(&self) -> String {
+        serde_json::to_string(&self.merged).unwrap_or_else(|_| "null".to_string())
+    }
+
+    /// `[{ path, kind, base, ours, theirs, resolution }]`
+    #[wasm_bindgen(getter)]
+    pub fn conflicts_json(&self) -> String {
+        serde_json::to_string(&self.conflicts).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+}
+
+/// Three-way merge of two edited versions of a component tree
+#[wasm_bindgen]
+pub fn merge_trees(
+    base_json: &str,
+    ours_json: &str,
+    theirs_json: &str,
+    options: &MergeOptions,
+) -> Result<MergeResult, JsValue> {
+    let parse = |json: &str, name: &str| {
+        serde_json::from_str::<serde_json::Value>(json)
+            .map_err(|e| JsValue::from_str(&format!("Failed to parse {} tree: {}", name, e)))
+    };
+    let (base, ours, theirs) = (parse(base_json, "base")?, parse(ours_json, "our")?, parse(theirs_json, "their")?);
+    Ok(merge_values(&base, &ours, &theirs, options))
+}
+
+fn merge_values(
+    base: &serde_json::Value,
+    ours: &serde_json::Value,
+    theirs: &serde_json::Value,
+    options: &MergeOptions,
+) -> MergeResult {
+    let mut merger = Merger { options, conflicts: Vec::new() };
+    let merged = merger.merge(Some(base), Some(ours), Some(theirs), "");
+    MergeResult {
+        merged: merged.unwrap_or_default(),
+        conflicts: merger.conflicts,
+    }
+}
+
+struct Merger<'a> {
+    options: &'a MergeOptions,
+    conflicts: Vec<MergeConflict>,
+}
+
+impl Merger<'_> {
+    /// The merged value; `None` if it is absent after the merge
+    fn merge(
+        &mut self,
+        base: Option<&serde_json::Value>,
+        ours: Option<&serde_json::Value>,
+        theirs: Option<&serde_json::Value>,
+        path: &str,
+    ) -> Option<serde_json::Value> {
+        use serde_json::Value;
+
+        if ours == theirs || theirs == base {
+            return ours.cloned();
+        }
+        if ours == base {
+            return theirs.cloned();
+        }
+        match (ours, theirs) {
+            (Some(o @ Value::Object(ours_map)), Some(t @ Value::Object(theirs_map))) if same_node(o, t) => {
+                let base_map = base.and_then(Value::as_object);
+                Some(self.merge_objects(base_map, ours_map, theirs_map, path, is_component(o)))
             }
-            _ => {}
+            (Some(_), Some(_)) if base.is_none() => self.conflict(MergeConflictKind::BothAdded, base, ours, theirs, path),
+            (Some(_), Some(_)) => self.conflict(MergeConflictKind::BothChanged, base, ours, theirs, path),
+            _ => self.conflict(MergeConflictKind::EditRemove, base, ours, theirs, path),
         }
-        return;
     }
 
-    // Navigate to parent
-    let mut current = tree;
-    for (i, part) in path_parts.iter().enumerate() {
-        if i == path_parts.len() - 1 {
-            // This is the target
-            match patch.change_type.as_str() {
-                "add" | "update" => {
-                    if let Some(new_val) = &patch.new_value {
-                        if let Ok(val) = serde_json::from_str(new_val) {
-                            if let Ok(idx) = part.parse::<usize>() {
-                                if let Some(arr) = current.as_array_mut() {
-                                    if idx < arr.len() {
-                                        arr[idx] = val;
-                                    } else {
-                                        arr.push(val);
-                                    }
-                                }
-                            } else if let Some(obj) = current.as_object_mut() {
-                                obj.insert(part.to_string(), val);
-                            }
-                        }
-                    }
-                }
-                "remove" => {
-                    if let Ok(idx) = part.parse::<usize>() {
-                        if let Some(arr) = current.as_array_mut() {
-                            if idx < arr.len() {
-                                arr.remove(idx);
-                            }
-                        }
-                    } else if let Some(obj) = current.as_object_mut() {
-                        obj.remove(*part);
-                    }
+    fn conflict(
+        &mut self,
+        kind: MergeConflictKind,
+        base: Option<&serde_json::Value>,
+        ours: Option<&serde_json::Value>,
+        theirs: Option<&serde_json::Value>,
+        path: &str,
+    ) -> Option<serde_json::Value> {
+        let resolution = self.options.winner();
+        self.conflicts.push(MergeConflict {
+            path: path.to_string(),
+            kind,
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+            resolution,
+        });
+        match resolution {
+            MergeSide::Ours => ours.cloned(),
+            MergeSide::Theirs => theirs.cloned(),
+        }
+    }
+
+    fn merge_objects(
+        &mut self,
+        base: Option<&serde_json::Map<String, serde_json::Value>>,
+        ours: &serde_json::Map<String, serde_json::Value>,
+        theirs: &serde_json::Map<String, serde_json::Value>,
+        path: &str,
+        component: bool,
+    ) -> serde_json::Value {
+        let keys: std::collections::BTreeSet<&String> =
+            ours.keys().chain(theirs.keys()).chain(base.into_iter().flat_map(|b| b.keys())).collect();
+        let mut merged = serde_json::Map::new();
+        for key in keys {
+            let (b, o, t) = (base.and_then(|b| b.get(key)), ours.get(key), theirs.get(key));
+            let key_path = child_path(path, key);
+            let value = match (o, t) {
+                (Some(serde_json::Value::Array(o)), Some(serde_json::Value::Array(t))) if component && key == "children" => {
+                    let b = b.and_then(serde_json::Value::as_array).map_or(&[][..], Vec::as_slice);
+                    Some(serde_json::Value::Array(self.merge_children(b, o, t, &key_path)))
                 }
-                _ => {}
+                _ => self.merge(b, o, t, &key_path),
+            };
+            if let Some(value) = value {
+                merged.insert(key.clone(), value);
             }
+        }
+        serde_json::Value::Object(merged)
+    }
+
+    /// Merge children matched as in `merge_identities`; the order follows
+    /// the side that reordered them, with children only the other side has
+    /// inserted after their predecessor there
+    fn merge_children(
+        &mut self,
+        base: &[serde_json::Value],
+        ours: &[serde_json::Value],
+        theirs: &[serde_json::Value],
+        path: &str,
+    ) -> Vec<serde_json::Value> {
+        let (base_ids, ours_ids, theirs_ids) = merge_identities(base, ours, theirs);
+        let index = |ids: &[MergeIdentity], id: &MergeIdentity| ids.iter().position(|other| other == id);
+
+        // Relative order of the children every version has
+        let common = |ids: &[MergeIdentity]| -> Vec<MergeIdentity> {
+            ids.iter()
+                .filter(|id| index(&base_ids, id).is_some() && index(&ours_ids, id).is_some() && index(&theirs_ids, id).is_some())
+                .cloned()
+                .collect()
+        };
+        let (base_order, ours_order, theirs_order) = (common(&base_ids), common(&ours_ids), common(&theirs_ids));
+        let theirs_first = if ours_order != base_order && theirs_order != base_order && ours_order != theirs_order {
+            let children = |children: &[serde_json::Value]| Some(serde_json::Value::Array(children.to_vec()));
+            self.conflicts.push(MergeConflict {
+                path: path.to_string(),
+                kind: MergeConflictKind::Reordered,
+                base: children(base),
+                ours: children(ours),
+                theirs: children(theirs),
+                resolution: self.options.winner(),
+            });
+            self.options.winner() == MergeSide::Theirs
         } else {
-            // Navigate deeper
-            if let Ok(idx) = part.parse::<usize>() {
-                if let Some(arr) = current.as_array_mut() {
-                    if idx < arr.len() {
-                        current = &mut arr[idx];
-                    } else {
-                        return;
-                    }
-                } else {
-                    return;
-                }
-            } else if let Some(obj) = current.as_object_mut() {
-                if let Some(val) = obj.get_mut(*part) {
-                    current = val;
-                } else {
-                    return;
+            ours_order == base_order && theirs_order != base_order
+        };
+        let (primary, secondary) = if theirs_first { (&theirs_ids, &ours_ids) } else { (&ours_ids, &theirs_ids) };
+
+        let mut order: Vec<&MergeIdentity> = primary.iter().collect();
+        let mut anchor: Option<usize> = None;
+        for id in secondary {
+            match order.iter().position(|placed| *placed == id) {
+                Some(position) => anchor = Some(position),
+                None => {
+                    let position = anchor.map_or(0, |p| p + 1);
+                    order.insert(position, id);
+                    anchor = Some(position);
                 }
-            } else {
-                return;
             }
         }
+
+        // Merged in their final order, so conflicts carry merged indices
+        let mut merged = Vec::with_capacity(order.len());
+        for id in order {
+            let (b, o, t) = (index(&base_ids, id), index(&ours_ids, id), index(&theirs_ids, id));
+            let child = child_path(path, &merged.len().to_string());
+            if let Some(value) = self.merge(b.map(|i| &base[i]), o.map(|i| &ours[i]), t.map(|i| &theirs[i]), &child) {
+                merged.push(value);
+            }
+        }
+        merged
+    }
+}
+
+/// How a child is matched across the versions in a merge
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MergeIdentity {
+    Keyed(String),
+    /// An unkeyed child, by its index in the base
+    Base(usize),
+    /// An unkeyed child one side inserted, by its index there
+    Inserted(MergeSide, usize),
+}
+
+/// Keyed children match by key. Each side's unkeyed children are aligned
+/// with the base's by a longest common subsequence that pairs children of
+/// the same type, preferring equal ones, so an insert does not shift the
+/// children after it; equal unkeyed inserts on both sides are one child.
+fn merge_identities(
+    base: &[serde_json::Value],
+    ours: &[serde_json::Value],
+    theirs: &[serde_json::Value],
+) -> (Vec<MergeIdentity>, Vec<MergeIdentity>, Vec<MergeIdentity>) {
+    let keyed = |children: &[serde_json::Value]| -> Vec<Option<String>> {
+        child_identities(children)
+            .into_iter()
+            .map(|id| match id {
+                ChildIdentity::Keyed(key) => Some(key),
+                ChildIdentity::Unkeyed(_) => None,
+            })
+            .collect()
+    };
+    let unkeyed = |keys: &[Option<String>]| -> Vec<usize> { (0..keys.len()).filter(|&i| keys[i].is_none()).collect() };
+    let (base_keys, ours_keys, theirs_keys) = (keyed(base), keyed(ours), keyed(theirs));
+    let base_unkeyed = unkeyed(&base_keys);
+
+    let side_ids = |children: &[serde_json::Value], keys: &[Option<String>], side: MergeSide| -> Vec<MergeIdentity> {
+        let side_unkeyed = unkeyed(keys);
+        let matched: HashMap<usize, usize> =
+            align(&base_unkeyed, &side_unkeyed, |b, s| similarity(&base[b], &children[s]))
+                .into_iter()
+                .map(|(b, s)| (s, b))
+                .collect();
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| match (key, matched.get(&i)) {
+                (Some(key), _) => MergeIdentity::Keyed(key.clone()),
+                (None, Some(&b)) => MergeIdentity::Base(b),
+                (None, None) => MergeIdentity::Inserted(side, i),
+            })
+            .collect()
+    };
+    let base_ids = base_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| key.clone().map_or(MergeIdentity::Base(i), MergeIdentity::Keyed))
+        .collect();
+    let ours_ids = side_ids(ours, &ours_keys, MergeSide::Ours);
+    let mut theirs_ids = side_ids(theirs, &theirs_keys, MergeSide::Theirs);
+
+    let inserted = |ids: &[MergeIdentity]| -> Vec<usize> {
+        (0..ids.len()).filter(|&i| matches!(ids[i], MergeIdentity::Inserted(..))).collect()
+    };
+    let equal = |o: usize, t: usize| usize::from(ours[o] == theirs[t]) * 2;
+    for (o, t) in align(&inserted(&ours_ids), &inserted(&theirs_ids), equal) {
+        theirs_ids[t] = MergeIdentity::Inserted(MergeSide::Ours, o);
+    }
+    (base_ids, ours_ids, theirs_ids)
+}
+
+/// 2 for equal values, 1 for the same kind of node, 0 for no match
+fn similarity(a: &serde_json::Value, b: &serde_json::Value) -> usize {
+    if a == b {
+        2
+    } else if std::mem::discriminant(a) == std::mem::discriminant(b) && same_node(a, b) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Highest scoring common subsequence of `a` and `b`, as pairs of the
+/// matched elements; elements scoring 0 never match
+fn align(a: &[usize], b: &[usize], score: impl Fn(usize, usize) -> usize) -> Vec<(usize, usize)> {
+    // best[i][j]: best total for a[i..] and b[j..]
+    let mut best = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            let pair = match score(a[i], b[j]) {
+                0 => 0,
+                s => s + best[i + 1][j + 1],
+            };
+            best[i][j] = pair.max(best[i + 1][j]).max(best[i][j + 1]);
+        }
+    }
+    let (mut i, mut j, mut pairs) = (0, 0, Vec::new());
+    while i < a.len() && j < b.len() {
+        if best[i][j] == best[i + 1][j] {
+            i += 1;
+        } else if best[i][j] == best[i][j + 1] {
+            j += 1;
+        } else {
+            pairs.push((a[i], b[j]));
+            i += 1;
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Whether two objects are the same node: both plain objects, or components
+/// with the same type and key
+fn same_node(a: &serde_json::Value, b: &serde_json::Value) -> bool {
+    match (is_component(a), is_component(b)) {
+        (false, false) => true,
+        (true, true) => a.get("type") == b.get("type") && component_key(a) == component_key(b),
+        _ => false,
     }
 }
 
@@ -349,4 +1069,389 @@ mod tests {
         assert_eq!(diffs.len(), 1);
         assert_eq!(diffs[0].path, "props.className");
     }
+
+    fn row(key: u32) -> serde_json::Value {
+        serde_json::json!({"type": "Row", "props": {"key": key, "label": format!("row {}", key)}})
+    }
+
+    fn list(keys: &[u32]) -> String {
+        let children: Vec<_> = keys.iter().map(|&k| row(k)).collect();
+        serde_json::json!({"type": "List", "props": {}, "children": children}).to_string()
+    }
+
+    fn change_types(old: &str, new: &str) -> Vec<String> {
+        let diffs = compute_diff(old, new, "");
+        let patched: serde_json::Value =
+            serde_json::from_str(&patch_tree(old, &serde_json::to_string(&diffs).unwrap()).unwrap()).unwrap();
+        assert_eq!(patched, serde_json::from_str::<serde_json::Value>(new).unwrap());
+        diffs.into_iter().map(|d| d.change_type).collect()
+    }
+
+    #[test]
+    fn test_keyed_children_diff() {
+        let rows: Vec<u32> = (1..=500).collect();
+        let mut prepended = vec![0];
+        prepended.extend(&rows);
+        assert_eq!(change_types(&list(&rows), &list(&prepended)), vec!["insert"]);
+        assert_eq!(change_types(&list(&[1, 2, 3, 4]), &list(&[2, 3, 4, 1])), vec!["move"]);
+        assert_eq!(change_types(&list(&[1, 2, 3, 4]), &list(&[4, 1, 3, 2])), vec!["move", "move"]);
+        assert_eq!(change_types(&list(&[1, 2, 3, 4, 5]), &list(&[5, 2, 6, 3])), vec!["remove", "remove", "insert", "move"]);
+
+        let diffs = compute_diff(&list(&[1, 2]), &list(&[2, 1]), "");
+        assert_eq!(diffs[0].from.as_deref(), Some("children.0"));
+        assert_eq!(diffs[0].path, "children.1");
+
+        // A keyed child's props are diffed at its new index
+        let mut updated = serde_json::from_str::<serde_json::Value>(&list(&[2, 1])).unwrap();
+        updated["children"][1]["props"]["label"] = "changed".into();
+        let diffs = compute_diff(&list(&[1, 2]), &updated.to_string(), "");
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(diffs[1].path, "children.1.props.label");
+    }
+
+    #[test]
+    fn test_component_replace() {
+        let old = r#"{"type": "div", "children": ["a", {"type": "span", "props": {"x": 1}}]}"#;
+        let new = r#"{"type": "div", "children": ["b", {"type": "em", "props": {"x": 1}}]}"#;
+        assert_eq!(change_types(old, new), vec!["update", "replace"]);
+        assert_eq!(change_types(old, r#"{"type": "section"}"#), vec!["replace"]);
+        assert_eq!(longest_increasing_subsequence(&[3, 0, 1, 2]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_json_patch_interop() {
+        let old = r#"{"type": "div", "props": {"a.b": 1, "x": "y"}, "children": [{"type": "li", "props": {"key": "k"}}]}"#;
+        let new = r#"{"type": "div", "props": {"a.b": 2}, "children": []}"#;
+
+        let diffs = compute_diff(old, new, "");
+        assert!(diffs.iter().any(|d| d.path == r"props.a\.b"));
+        let patch = diff_trees_json_patch(old, new);
+        let operations: serde_json::Value = serde_json::from_str(&patch).unwrap();
+        assert!(operations.as_array().unwrap().contains(&serde_json::json!(
+            {"op": "replace", "path": "/props/a.b", "value": 2}
+        )));
+
+        let expected: serde_json::Value = serde_json::from_str(new).unwrap();
+        let from_json_patch: serde_json::Value = serde_json::from_str(&patch_tree(old, &patch).unwrap()).unwrap();
+        assert_eq!(from_json_patch, expected);
+        let converted = json_patch_to_tree_diff(&patch).unwrap();
+        assert!(converted.contains(r#""path":"props.a\\.b""#));
+        let from_tree_diff: serde_json::Value = serde_json::from_str(&patch_tree(old, &converted).unwrap()).unwrap();
+        assert_eq!(from_tree_diff, expected);
+        assert_eq!(tree_diff_to_json_patch(&converted), patch);
+
+        // A failed test is an error in either format
+        let guarded = r#"[{"op": "remove", "path": "/props"}, {"op": "test", "path": "/type", "value": "span"}]"#;
+        assert!(patch_tree(old, guarded).unwrap_err().contains("test failed"));
+        assert!(patch_tree(old, r#"[{"op": "add", "path": "props"}]"#).is_err());
+        assert!(patch_tree(old, "not json").is_err());
+        let guarded = json_patch_to_tree_diff(guarded).unwrap();
+        assert!(patch_tree(old, &guarded).unwrap_err().contains("test failed"));
+        assert_eq!(split_path(r"a\\.b\.c"), vec![r"a\", "b.c"]);
+        assert_eq!(split_path(&child_path(&child_path("", ""), "")), vec!["", ""]);
+    }
+
+    /// Deterministic xorshift64 generator for the round-trip tests
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn chance(&mut self, percent: usize) -> bool {
+            self.below(100) < percent
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    const PROP_KEYS: &[&str] = &["class", "a.b", r"x\y", "", "on", "0"];
+
+    fn random_value(rng: &mut XorShift, depth: usize) -> serde_json::Value {
+        match rng.below(if depth == 0 { 4 } else { 6 }) {
+            0 => serde_json::Value::Null,
+            1 => rng.chance(50).into(),
+            2 => (rng.below(5) as i64 - 2).into(),
+            3 => rng.pick(&["", "a", "b.c", r"\"]).into(),
+            4 => (0..rng.below(4)).map(|_| random_value(rng, depth - 1)).collect(),
+            _ => random_props(rng, depth - 1),
+        }
+    }
+
+    fn random_props(rng: &mut XorShift, depth: usize) -> serde_json::Value {
+        let mut props = serde_json::Map::new();
+        for _ in 0..rng.below(4) {
+            props.insert(rng.pick(PROP_KEYS).to_string(), random_value(rng, depth));
+        }
+        serde_json::Value::Object(props)
+    }
+
+    fn random_child(rng: &mut XorShift, depth: usize) -> serde_json::Value {
+        if rng.chance(25) {
+            rng.pick(&["text", "more text", ""]).into()
+        } else {
+            random_component(rng, depth)
+        }
+    }
+
+    fn random_component(rng: &mut XorShift, depth: usize) -> serde_json::Value {
+        let mut component = serde_json::json!({"type": rng.pick(&["div", "span", "Row"])});
+        if rng.chance(80) {
+            let mut props = random_props(rng, 1);
+            if rng.chance(60) {
+                // Small key space, so repeated keys occur too
+                props["key"] = format!("k{}", rng.below(8)).into();
+            }
+            component["props"] = props;
+        }
+        if depth > 0 && rng.chance(80) {
+            component["children"] = (0..rng.below(6)).map(|_| random_child(rng, depth - 1)).collect();
+        }
+        component
+    }
+
+    /// An edited copy of `value`: reordered, inserted, removed and changed
+    /// children, props and types
+    fn mutate(rng: &mut XorShift, value: &serde_json::Value, depth: usize) -> serde_json::Value {
+        use serde_json::Value;
+
+        if rng.chance(5) {
+            return random_child(rng, depth);
+        }
+        match value {
+            Value::Object(map) => {
+                let mut map = map.clone();
+                if rng.chance(10) {
+                    map.insert("type".to_string(), rng.pick(&["div", "section"]).into());
+                }
+                for (key, entry) in map.iter_mut() {
+                    if key != "type" && rng.chance(30) {
+                        *entry = mutate(rng, entry, depth.saturating_sub(1));
+                    }
+                }
+                if rng.chance(15) {
+                    map.insert(rng.pick(PROP_KEYS).to_string(), random_value(rng, 1));
+                }
+                if rng.chance(10) {
+                    let key = rng.pick(&["props", "children", "class", "a.b"]);
+                    map.remove(key);
+                }
+                Value::Object(map)
+            }
+            Value::Array(items) => {
+                let mut items: Vec<Value> = items.iter().map(|item| {
+                    if rng.chance(30) { mutate(rng, item, depth.saturating_sub(1)) } else { item.clone() }
+                }).collect();
+                for _ in 0..rng.below(3) {
+                    match rng.below(3) {
+                        0 if !items.is_empty() => {
+                            let i = rng.below(items.len());
+                            items.remove(i);
+                        }
+                        1 => {
+                            let i = rng.below(items.len() + 1);
+                            items.insert(i, random_child(rng, depth));
+                        }
+                        _ if items.len() > 1 => {
+                            let (i, j) = (rng.below(items.len()), rng.below(items.len()));
+                            items.swap(i, j);
+                        }
+                        _ => {}
+                    }
+                }
+                Value::Array(items)
+            }
+            _ => random_value(rng, depth),
+        }
+    }
+
+    #[test]
+    fn test_patch_round_trip_property() {
+        for seed in 1..=500u64 {
+            let mut rng = XorShift(seed.wrapping_mul(0x9e3779b97f4a7c15));
+            let old = random_component(&mut rng, 3);
+            let new = if rng.chance(10) { random_component(&mut rng, 3) } else { mutate(&mut rng, &old, 3) };
+            let (old_json, new_json) = (old.to_string(), new.to_string());
+
+            let patched: serde_json::Value =
+                serde_json::from_str(&patch_tree(&old_json, &diff_trees(&old_json, &new_json)).unwrap()).unwrap();
+            assert_eq!(patched, new, "seed {}: {} -> {}", seed, old_json, new_json);
+            let patched: serde_json::Value =
+                serde_json::from_str(&patch_tree(&old_json, &diff_trees_json_patch(&old_json, &new_json)).unwrap()).unwrap();
+            assert_eq!(patched, new, "seed {} (JSON Patch): {} -> {}", seed, old_json, new_json);
+        }
+    }
+
+    #[test]
+    fn test_array_removal_and_nulls() {
+        let old = r#"{"items": [1, 2, 3, 4, 5], "a": 1}"#;
+        let new = r#"{"items": [1, 2, 3], "a": null}"#;
+        let diffs = compute_diff(old, new, "");
+        let paths: Vec<&str> = diffs.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["a", "items.4", "items.3"]);
+        assert_eq!(diffs[0].change_type, "update");
+        assert_eq!(patch_tree(old, &diff_trees(old, new)).unwrap(), r#"{"a":null,"items":[1,2,3]}"#);
+
+        // An add past the end fails the whole patch, including earlier diffs
+        let patch = r#"[{"path": "a", "change_type": "update", "old_value": null, "new_value": "2"},
+                        {"path": "items.9", "change_type": "add", "old_value": null, "new_value": "6"}]"#;
+        assert!(patch_tree(r#"{"a": 1, "items": [1]}"#, patch).unwrap_err().contains("out of bounds"));
+        let patch = r#"[{"path": "missing.b", "change_type": "update", "old_value": null, "new_value": "2"}]"#;
+        assert!(patch_tree(r#"{"a": 1}"#, patch).is_err());
+    }
+
+    fn merged(base: &str, ours: &str, theirs: &str, options: &MergeOptions) -> (serde_json::Value, Vec<MergeConflict>) {
+        let parse = |json: &str| serde_json::from_str::<serde_json::Value>(json).unwrap();
+        let result = merge_values(&parse(base), &parse(ours), &parse(theirs), options);
+        (result.merged, result.conflicts)
+    }
+
+    /// An unkeyed element with a text prop
+    fn el(kind: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({"type": kind, "props": {"text": text}})
+    }
+
+    fn page(children: &[serde_json::Value]) -> String {
+        serde_json::json!({"type": "Page", "props": {}, "children": children}).to_string()
+    }
+
+    #[test]
+    fn test_merge_unkeyed_children() {
+        let base = [el("h1", "Title"), el("p", "Intro"), el("p", "Body")];
+        let with_banner = |children: &[serde_json::Value]| {
+            let mut children = children.to_vec();
+            children.insert(0, el("Banner", "Sale"));
+            children
+        };
+        let options = MergeOptions::new(MergePolicy::Theirs);
+
+        // An insert on one side does not shift an edit on the other
+        let theirs = [el("h1", "Title"), el("p", "Intro"), el("p", "Body v2")];
+        let (tree, conflicts) = merged(&page(&base), &page(&with_banner(&base)), &page(&theirs), &options);
+        assert!(conflicts.is_empty());
+        assert_eq!(tree.to_string(), page(&with_banner(&theirs)));
+
+        // Conflicts are reported at their index in the merged tree
+        let ours = with_banner(&[el("h1", "Ours"), el("p", "Intro"), el("p", "Body")]);
+        let theirs = [el("h1", "Theirs"), el("p", "Intro"), el("p", "Body")];
+        let (tree, conflicts) = merged(&page(&base), &page(&ours), &page(&theirs), &options);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "children.1.props.text");
+        assert_eq!(tree["children"][1], el("h1", "Theirs"));
+
+        // Equal inserts on both sides are kept once, different ones both
+        let mut footer = base.to_vec();
+        footer.push(el("footer", "A"));
+        let (tree, _) = merged(&page(&base), &page(&footer), &page(&footer), &options);
+        assert_eq!(tree.to_string(), page(&footer));
+        let mut other = base.to_vec();
+        other.push(el("footer", "B"));
+        let (tree, conflicts) = merged(&page(&base), &page(&footer), &page(&other), &options);
+        assert!(conflicts.is_empty());
+        assert_eq!(tree["children"].as_array().unwrap()[3..], [el("footer", "B"), el("footer", "A")]);
+
+        // Text children pair with an edited text rather than an inserted one
+        let text = |children: &[&str]| serde_json::json!({"type": "p", "children": children}).to_string();
+        let (tree, conflicts) = merged(&text(&["x"]), &text(&["new", "x"]), &text(&["y"]), &options);
+        assert!(conflicts.is_empty());
+        assert_eq!(tree.to_string(), text(&["new", "y"]));
+    }
+
+    #[test]
+    fn test_merge_disjoint_edits() {
+        let base = list(&[1, 2, 3]);
+        let mut ours: serde_json::Value = serde_json::from_str(&list(&[3, 1, 2])).unwrap();
+        ours["props"]["title"] = serde_json::json!("Rows");
+        let mut theirs: serde_json::Value = serde_json::from_str(&list(&[1, 2, 4, 3])).unwrap();
+        theirs["children"][1]["props"]["label"] = serde_json::json!("second");
+
+        let options = MergeOptions::new(MergePolicy::Ours);
+        let (tree, conflicts) = merged(&base, &ours.to_string(), &theirs.to_string(), &options);
+        assert!(conflicts.is_empty());
+        let mut expected: serde_json::Value = serde_json::from_str(&list(&[3, 1, 2, 4])).unwrap();
+        expected["props"]["title"] = serde_json::json!("Rows");
+        expected["children"][2]["props"]["label"] = serde_json::json!("second");
+        assert_eq!(tree, expected);
+
+        // Rows added on both sides with the same content merge cleanly
+        let (tree, conflicts) = merged(&base, &list(&[1, 2, 3, 5]), &list(&[1, 2, 3, 5]), &options);
+        assert!(conflicts.is_empty());
+        assert_eq!(tree.to_string(), list(&[1, 2, 3, 5]));
+    }
+
+    #[test]
+    fn test_merge_conflict_policies() {
+        let base = r#"{"type": "Button", "props": {"label": "Save", "size": 1}}"#;
+        let ours = r#"{"type": "Button", "props": {"label": "Store", "size": 1}}"#;
+        let theirs = r#"{"type": "Button", "props": {"label": "Keep", "size": 2}}"#;
+        let label = |options: &MergeOptions| {
+            let (tree, conflicts) = merged(base, ours, theirs, options);
+            assert_eq!(tree["props"]["size"], 2);
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].path, "props.label");
+            assert_eq!(conflicts[0].kind, MergeConflictKind::BothChanged);
+            tree["props"]["label"].clone()
+        };
+        assert_eq!(label(&MergeOptions::new(MergePolicy::Ours)), "Store");
+        assert_eq!(label(&MergeOptions::new(MergePolicy::Theirs)), "Keep");
+
+        let mut options = MergeOptions::new(MergePolicy::LastWriterWins);
+        options.set_timestamps(200.0, 100.0);
+        assert_eq!(label(&options), "Store");
+        options.set_timestamps(100.0, 200.0);
+        assert_eq!(label(&options), "Keep");
+        options.set_timestamps(100.0, 100.0);
+        assert_eq!(label(&options), "Store");
+
+        let result = merge_values(
+            &serde_json::from_str(base).unwrap(),
+            &serde_json::from_str(ours).unwrap(),
+            &serde_json::from_str(theirs).unwrap(),
+            &options,
+        );
+        assert!(result.has_conflicts());
+        assert_eq!(
+            result.conflicts_json(),
+            r#"[{"path":"props.label","kind":"both_changed","base":"Save","ours":"Store","theirs":"Keep","resolution":"ours"}]"#
+        );
+    }
+
+    #[test]
+    fn test_merge_structural_conflicts() {
+        // We removed row 2 while they edited it
+        let base = list(&[1, 2, 3]);
+        let mut theirs: serde_json::Value = serde_json::from_str(&base).unwrap();
+        theirs["children"][1]["props"]["label"] = serde_json::json!("edited");
+        let theirs = theirs.to_string();
+
+        let (tree, conflicts) = merged(&base, &list(&[1, 3]), &theirs, &MergeOptions::new(MergePolicy::Ours));
+        assert_eq!(tree.to_string(), list(&[1, 3]));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, MergeConflictKind::EditRemove);
+        assert_eq!(conflicts[0].path, "children.1");
+        assert_eq!(conflicts[0].ours, None);
+
+        let (tree, _) = merged(&base, &list(&[1, 3]), &theirs, &MergeOptions::new(MergePolicy::Theirs));
+        assert_eq!(tree, serde_json::from_str::<serde_json::Value>(&theirs).unwrap());
+
+        // Both sides reordered differently, and both replaced the root type
+        let (tree, conflicts) = merged(&base, &list(&[3, 2, 1]), &list(&[2, 1, 3]), &MergeOptions::new(MergePolicy::Theirs));
+        assert_eq!(tree.to_string(), list(&[2, 1, 3]));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].kind, conflicts[0].path.as_str()), (MergeConflictKind::Reordered, "children"));
+
+        let (tree, conflicts) = merged(&base, r#"{"type": "Grid"}"#, r#"{"type": "Table"}"#, &MergeOptions::new(MergePolicy::Ours));
+        assert_eq!(tree, serde_json::json!({"type": "Grid"}));
+        assert_eq!((conflicts[0].kind, conflicts[0].path.as_str()), (MergeConflictKind::BothChanged, ""));
+    }
 }
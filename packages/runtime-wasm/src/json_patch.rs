@@ -0,0 +1,260 @@
+//! JSON Patch (RFC 6902) with JSON Pointer (RFC 6901) paths
+//!
+//! Used by `hydrate.rs` to exchange tree diffs with other tools. A patch is
+//! applied atomically: if any operation fails (including a `test`), the
+//! document is left unchanged.
+
+use serde_json::{json, Value};
+
+/// One patch operation; paths are unescaped pointer tokens
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Operation {
+    Add { path: Vec<String>, value: Value },
+    Remove { path: Vec<String> },
+    Replace { path: Vec<String>, value: Value },
+    Move { from: Vec<String>, path: Vec<String> },
+    Copy { from: Vec<String>, path: Vec<String> },
+    Test { path: Vec<String>, value: Value },
+}
+
+impl Operation {
+    /// Parse one operation object, e.g. `{ "op": "add", "path": "/a", "value": 1 }`
+    pub(crate) fn from_json(operation: &Value) -> Result<Self, String> {
+        let field = |name: &str| {
+            operation
+                .get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| format!("operation is missing \"{}\"", name))
+        };
+        let pointer = |name: &str| field(name).and_then(parse_pointer);
+        // `value` may be null, so only a missing member is an error
+        let value = || operation.get("value").cloned().ok_or_else(|| "operation is missing \"value\"".to_string());
+        Ok(match field("op")? {
+            "add" => Self::Add { path: pointer("path")?, value: value()? },
+            "remove" => Self::Remove { path: pointer("path")? },
+            "replace" => Self::Replace { path: pointer("path")?, value: value()? },
+            "move" => Self::Move { from: pointer("from")?, path: pointer("path")? },
+            "copy" => Self::Copy { from: pointer("from")?, path: pointer("path")? },
+            "test" => Self::Test { path: pointer("path")?, value: value()? },
+            other => return Err(format!("unknown op \"{}\"", other)),
+        })
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        match self {
+            Self::Add { path, value } => json!({ "op": "add", "path": format_pointer(path), "value": value }),
+            Self::Remove { path } => json!({ "op": "remove", "path": format_pointer(path) }),
+            Self::Replace { path, value } => json!({ "op": "replace", "path": format_pointer(path), "value": value }),
+            Self::Move { from, path } => {
+                json!({ "op": "move", "from": format_pointer(from), "path": format_pointer(path) })
+            }
+            Self::Copy { from, path } => {
+                json!({ "op": "copy", "from": format_pointer(from), "path": format_pointer(path) })
+            }
+            Self::Test { path, value } => json!({ "op": "test", "path": format_pointer(path), "value": value }),
+        }
+    }
+
+    fn apply(&self, document: &mut Value) -> Result<(), String> {
+        match self {
+            Self::Add { path, value } => add(document, path, value.clone()),
+            Self::Remove { path } => remove(document, path).map(drop),
+            Self::Replace { path, value } => {
+                *get_mut(document, path)? = value.clone();
+                Ok(())
+            }
+            Self::Move { from, path } => {
+                if path.len() > from.len() && path.starts_with(from) {
+                    return Err(format!("cannot move {} into itself", format_pointer(from)));
+                }
+                let value = remove(document, from)?;
+                add(document, path, value)
+            }
+            Self::Copy { from, path } => {
+                let value = get(document, from)?.clone();
+                add(document, path, value)
+            }
+            Self::Test { path, value } => {
+                if get(document, path)? == value {
+                    Ok(())
+                } else {
+                    Err(format!("test failed at {}", format_pointer(path)))
+                }
+            }
+        }
+    }
+}
+
+/// Parse a patch document (an array of operations)
+pub(crate) fn parse_patch(patch: &Value) -> Result<Vec<Operation>, String> {
+    patch
+        .as_array()
+        .ok_or_else(|| "a JSON Patch must be an array".to_string())?
+        .iter()
+        .enumerate()
+        .map(|(i, operation)| Operation::from_json(operation).map_err(|e| format!("operation {}: {}", i, e)))
+        .collect()
+}
+
+/// Whether a parsed patch is a JSON Patch (every entry has an `op`)
+pub(crate) fn is_json_patch(patch: &Value) -> bool {
+    patch
+        .as_array()
+        .is_some_and(|ops| !ops.is_empty() && ops.iter().all(|op| op.get("op").is_some_and(Value::is_string)))
+}
+
+/// Apply every operation, or none if one fails
+pub(crate) fn apply(document: &mut Value, operations: &[Operation]) -> Result<(), String> {
+    let mut patched = document.clone();
+    for operation in operations {
+        operation.apply(&mut patched)?;
+    }
+    *document = patched;
+    Ok(())
+}
+
+/// Split a JSON Pointer into unescaped tokens; `""` is the whole document
+pub(crate) fn parse_pointer(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(format!("invalid JSON Pointer \"{}\"", pointer));
+    };
+    Ok(rest.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+pub(crate) fn format_pointer(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("/{}", token.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// An array index token: digits without leading zeros
+fn array_index(token: &str) -> Result<usize, String> {
+    if token.is_empty() || !token.bytes().all(|b| b.is_ascii_digit()) || (token.len() > 1 && token.starts_with('0')) {
+        return Err(format!("invalid array index \"{}\"", token));
+    }
+    token.parse().map_err(|_| format!("invalid array index \"{}\"", token))
+}
+
+pub(crate) fn get<'a>(document: &'a Value, path: &[String]) -> Result<&'a Value, String> {
+    let mut current = document;
+    for token in path {
+        current = match current {
+            Value::Array(arr) => arr.get(array_index(token)?),
+            Value::Object(obj) => obj.get(token),
+            _ => None,
+        }
+        .ok_or_else(|| format!("no value at {}", format_pointer(path)))?;
+    }
+    Ok(current)
+}
+
+pub(crate) fn get_mut<'a>(document: &'a mut Value, path: &[String]) -> Result<&'a mut Value, String> {
+    let mut current = document;
+    for token in path {
+        current = match current {
+            Value::Array(arr) => arr.get_mut(array_index(token)?),
+            Value::Object(obj) => obj.get_mut(token),
+            _ => None,
+        }
+        .ok_or_else(|| format!("no value at {}", format_pointer(path)))?;
+    }
+    Ok(current)
+}
+
+/// Insert into an array (`-` appends) or set an object member
+pub(crate) fn add(document: &mut Value, path: &[String], value: Value) -> Result<(), String> {
+    let Some((last, parent)) = path.split_last() else {
+        *document = value;
+        return Ok(());
+    };
+    match get_mut(document, parent)? {
+        Value::Array(arr) if last == "-" => arr.push(value),
+        Value::Array(arr) => {
+            let idx = array_index(last)?;
+            if idx > arr.len() {
+                return Err(format!("index {} is out of bounds", format_pointer(path)));
+            }
+            arr.insert(idx, value);
+        }
+        Value::Object(obj) => {
+            obj.insert(last.clone(), value);
+        }
+        _ => return Err(format!("cannot add to {}", format_pointer(parent))),
+    }
+    Ok(())
+}
+
+/// Remove and return an array element or object member
+pub(crate) fn remove(document: &mut Value, path: &[String]) -> Result<Value, String> {
+    let Some((last, parent)) = path.split_last() else {
+        return Ok(std::mem::take(document));
+    };
+    let missing = || format!("no value at {}", format_pointer(path));
+    match get_mut(document, parent)? {
+        Value::Array(arr) => {
+            let idx = array_index(last)?;
+            if idx >= arr.len() {
+                return Err(missing());
+            }
+            Ok(arr.remove(idx))
+        }
+        Value::Object(obj) => obj.remove(last).ok_or_else(missing),
+        _ => Err(missing()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patched(document: Value, patch: Value) -> Result<Value, String> {
+        let mut document = document;
+        apply(&mut document, &parse_patch(&patch)?)?;
+        Ok(document)
+    }
+
+    #[test]
+    fn test_pointers() {
+        let tokens = parse_pointer("/props/a~1b/c.d~0").unwrap();
+        assert_eq!(tokens, vec!["props", "a/b", "c.d~"]);
+        assert_eq!(format_pointer(&tokens), "/props/a~1b/c.d~0");
+        assert_eq!(parse_pointer("").unwrap(), Vec::<String>::new());
+        assert!(parse_pointer("props").is_err());
+        assert!(array_index("01").is_err());
+    }
+
+    #[test]
+    fn test_operations() {
+        let document = json!({"a": {"b": [1, 2]}, "c": null});
+        let result = patched(
+            document.clone(),
+            json!([
+                {"op": "test", "path": "/c", "value": null},
+                {"op": "add", "path": "/a/b/0", "value": 0},
+                {"op": "add", "path": "/a/b/-", "value": 3},
+                {"op": "copy", "from": "/a/b", "path": "/d"},
+                {"op": "move", "from": "/a/b/1", "path": "/e"},
+                {"op": "replace", "path": "/c", "value": "x"},
+                {"op": "remove", "path": "/d/0"}
+            ]),
+        );
+        assert_eq!(result.unwrap(), json!({"a": {"b": [0, 2, 3]}, "c": "x", "d": [1, 2, 3], "e": 1}));
+
+        // Failing operations leave the document unchanged
+        let mut unchanged = document.clone();
+        let operations = parse_patch(&json!([
+            {"op": "remove", "path": "/c"},
+            {"op": "test", "path": "/a/b/0", "value": 5}
+        ]))
+        .unwrap();
+        assert!(apply(&mut unchanged, &operations).is_err());
+        assert_eq!(unchanged, document);
+        assert!(patched(document.clone(), json!([{"op": "move", "from": "/a", "path": "/a/x"}])).is_err());
+        assert!(patched(document.clone(), json!([{"op": "replace", "path": "/missing", "value": 1}])).is_err());
+        assert!(patched(document, json!([{"op": "add", "path": "/a/b/3", "value": 1}])).is_err());
+    }
+}
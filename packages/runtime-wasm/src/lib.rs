@@ -11,11 +11,21 @@ mod router;
 mod hydrate;
 mod render;
 mod skeleton;
+mod path;
+mod rules;
+mod template;
+mod snapshot;
+mod speculation;
+mod json_patch;
+mod crdt;
 
 pub use router::*;
+pub use rules::*;
+pub use speculation::*;
 pub use hydrate::*;
 pub use render::*;
 pub use skeleton::*;
+pub use crdt::*;
 
 #[wasm_bindgen(start)]
 pub fn init() {
@@ -29,14 +39,32 @@ pub fn init() {
 pub struct RouteDefinition {
     /// Pattern like "/blog/[slug]" or "/api/[...path]"
     pattern: String,
-    /// Session ID template, e.g., "blog-$slug"
+    /// Session ID template, e.g., "blog-$slug" or "blog-${slug|slugify}"
+    #[serde(alias = "sessionId")]
     session_id: String,
     /// Component ID reference
+    #[serde(alias = "componentId")]
     component_id: String,
     /// Optional layout wrapper
     layout: Option<String>,
     /// Whether this route uses 'use aeon' directive
+    #[serde(default, alias = "isAeon")]
     is_aeon: bool,
+    /// Optional stable name for lookups and `url_for`
+    #[serde(default)]
+    name: Option<String>,
+    /// Host pattern like "[tenant].example.com"; the route only matches
+    /// requests for matching hosts
+    #[serde(default)]
+    host: Option<String>,
+    /// HTTP methods the route accepts, e.g. ["GET", "POST"]; any if omitted
+    #[serde(default)]
+    methods: Option<Vec<String>>,
+    /// Arbitrary per-route data (cache TTL, auth, revalidate, prerender...).
+    /// `prerender` and `speculation` (`"prefetch"`, `"prerender"` or
+    /// `false`) control the route's Speculation Rules.
+    #[serde(default)]
+    metadata: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 #[wasm_bindgen]
@@ -55,6 +83,10 @@ impl RouteDefinition {
             component_id,
             layout,
             is_aeon,
+            name: None,
+            host: None,
+            methods: None,
+            metadata: None,
         }
     }
 
@@ -82,6 +114,58 @@ impl RouteDefinition {
     pub fn is_aeon(&self) -> bool {
         self.is_aeon
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn host(&self) -> Option<String> {
+        self.host.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_host(&mut self, host: Option<String>) {
+        self.host = host;
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn methods(&self) -> Option<Vec<String>> {
+        self.methods.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_methods(&mut self, methods: Option<Vec<String>>) {
+        self.methods = methods;
+    }
+
+    /// Metadata as a JSON object (`{}` if none)
+    #[wasm_bindgen(getter)]
+    pub fn metadata_json(&self) -> String {
+        metadata_to_json(&self.metadata)
+    }
+
+    /// Set metadata from a JSON object
+    pub fn set_metadata_json(&mut self, json: &str) -> Result<(), JsValue> {
+        self.metadata = Some(
+            serde_json::from_str(json)
+                .map_err(|e| JsValue::from_str(&format!("Failed to parse route metadata: {}", e)))?,
+        );
+        Ok(())
+    }
+}
+
+fn metadata_to_json(metadata: &Option<serde_json::Map<String, serde_json::Value>>) -> String {
+    metadata
+        .as_ref()
+        .and_then(|m| serde_json::to_string(m).ok())
+        .unwrap_or_else(|| "{}".to_string())
 }
 
 /// Match result with extracted parameters
@@ -94,6 +178,22 @@ pub struct RouteMatch {
     params: HashMap<String, String>,
     /// The resolved session ID with params substituted
     resolved_session_id: String,
+    /// Layout IDs wrapping the page, outermost first
+    layouts: Vec<String>,
+    /// The request path after normalization (dot segments resolved, canonical encoding)
+    normalized_path: String,
+    /// Decoded query string parameters; repeated keys keep every value
+    query: HashMap<String, Vec<String>>,
+    /// URL fragment without the leading `#`
+    fragment: Option<String>,
+    /// Locale from the path prefix, or the default locale (`None` without i18n)
+    locale: Option<String>,
+    /// Parallel route slot of the matched route (`None` for the main page)
+    slot: Option<String>,
+    /// Best match of every parallel route slot, by slot name
+    slots: HashMap<String, RouteMatch>,
+    /// Intercepting route that takes over the navigation (see `AeonRouter::match_route_from`)
+    intercepted: Option<Box<RouteMatch>>,
 }
 
 #[wasm_bindgen]
@@ -113,9 +213,91 @@ impl RouteMatch {
         self.resolved_session_id.clone()
     }
 
+    /// Layout chain as a JSON array, outermost layout first
+    #[wasm_bindgen(getter)]
+    pub fn layouts_json(&self) -> String {
+        serde_json::to_string(&self.layouts).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn normalized_path(&self) -> String {
+        self.normalized_path.clone()
+    }
+
     pub fn get_param(&self, key: &str) -> Option<String> {
         self.params.get(key).cloned()
     }
+
+    /// Name of the matched route
+    #[wasm_bindgen(getter)]
+    pub fn name(&self) -> Option<String> {
+        self.route.name.clone()
+    }
+
+    /// Metadata of the matched route as a JSON object
+    #[wasm_bindgen(getter)]
+    pub fn metadata_json(&self) -> String {
+        metadata_to_json(&self.route.metadata)
+    }
+
+    /// A single metadata value as JSON
+    pub fn get_metadata(&self, key: &str) -> Option<String> {
+        self.route
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .map(|v| v.to_string())
+    }
+
+    /// Query parameters as JSON: `{ "tag": ["a", "b"] }`
+    #[wasm_bindgen(getter)]
+    pub fn query_json(&self) -> String {
+        serde_json::to_string(&self.query).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// First value of a query parameter
+    pub fn get_query(&self, key: &str) -> Option<String> {
+        self.query.get(key).and_then(|values| values.first()).cloned()
+    }
+
+    /// All values of a query parameter as a JSON array
+    pub fn get_query_all(&self, key: &str) -> String {
+        serde_json::to_string(self.query.get(key).unwrap_or(&Vec::new()))
+            .unwrap_or_else(|_| "[]".to_string())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn fragment(&self) -> Option<String> {
+        self.fragment.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn locale(&self) -> Option<String> {
+        self.locale.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn slot(&self) -> Option<String> {
+        self.slot.clone()
+    }
+
+    /// Names of the slots that matched as a JSON array
+    #[wasm_bindgen(getter)]
+    pub fn slot_names_json(&self) -> String {
+        let mut names: Vec<&String> = self.slots.keys().collect();
+        names.sort();
+        serde_json::to_string(&names).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// The match of a single parallel route slot
+    pub fn get_slot(&self, name: &str) -> Option<RouteMatch> {
+        self.slots.get(name).cloned()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn intercepted(&self) -> Option<RouteMatch> {
+        self.intercepted.as_deref().cloned()
+    }
 }
 
 /// Serialized component tree (stored in Aeon sessions)
@@ -0,0 +1,189 @@
+//! URL path normalization shared by the router
+//!
+//! Normalization runs in this order:
+//! 1. Empty segments are dropped (`//about` is `/about`)
+//! 2. Dot segments are resolved (`/a/./b/../c` is `/a/c`); `..` never climbs
+//!    above the root. Percent-encoded dots (`%2E`) are not dot segments.
+//! 3. Each segment is percent-decoded on its own, so an encoded `%2F` stays
+//!    inside one segment instead of splitting it.
+//!
+//! The query string and fragment are split off first (`split_url`).
+
+use std::collections::HashMap;
+
+/// A path split into decoded segments
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NormalizedPath {
+    /// Percent-decoded segments
+    pub segments: Vec<String>,
+    /// Whether the input ended with a `/` (always false for the root)
+    pub trailing_slash: bool,
+}
+
+impl NormalizedPath {
+    /// Canonical encoded form: `/` + re-encoded segments
+    pub fn to_path(&self, trailing_slash: bool) -> String {
+        let mut path = String::from("/");
+        path.push_str(
+            &self
+                .segments
+                .iter()
+                .map(|s| percent_encode_segment(s))
+                .collect::<Vec<_>>()
+                .join("/"),
+        );
+        if trailing_slash && !self.segments.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+}
+
+/// Split, resolve and decode a URL path
+pub(crate) fn normalize_path(path: &str) -> NormalizedPath {
+    let mut raw_segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                raw_segments.pop();
+            }
+            _ => raw_segments.push(segment),
+        }
+    }
+
+    let last = path.rsplit('/').next().unwrap_or("");
+    let trailing_slash = !raw_segments.is_empty() && matches!(last, "" | "." | "..");
+
+    NormalizedPath {
+        segments: raw_segments
+            .into_iter()
+            .map(|s| percent_decode(s).unwrap_or_else(|| s.to_string()))
+            .collect(),
+        trailing_slash,
+    }
+}
+
+/// Split a URL into path, query and fragment (without the `?` / `#`)
+pub(crate) fn split_url(url: &str) -> (&str, Option<&str>, Option<&str>) {
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url, None),
+    };
+    let (path, query) = match rest.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (rest, None),
+    };
+    (path, query, fragment)
+}
+
+/// Parse `a=1&b=2&a=3` into a multi-valued map. `+` decodes to a space; keys
+/// without `=` get an empty value.
+pub(crate) fn parse_query(query: &str) -> HashMap<String, Vec<String>> {
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        map.entry(decode_query_component(key))
+            .or_default()
+            .push(decode_query_component(value));
+    }
+    map
+}
+
+fn decode_query_component(component: &str) -> String {
+    let component = component.replace('+', " ");
+    percent_decode(&component).unwrap_or(component)
+}
+
+/// Decode `%XX` escapes. Malformed escapes are kept literally; returns
+/// `None` if the decoded bytes are not valid UTF-8.
+pub(crate) fn percent_decode(input: &str) -> Option<String> {
+    if !input.contains('%') {
+        return Some(input.to_string());
+    }
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                decoded.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Encode everything except RFC 3986 unreserved characters, sub-delims, `:` and `@`
+pub(crate) fn percent_encode_segment(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        let n = normalize_path("//blog/./drafts/../hello%20world/");
+        assert_eq!(n.segments, vec!["blog", "hello world"]);
+        assert!(n.trailing_slash);
+        assert_eq!(n.to_path(false), "/blog/hello%20world");
+        assert_eq!(n.to_path(true), "/blog/hello%20world/");
+
+        let n = normalize_path("/../a%2Fb");
+        assert_eq!(n.segments, vec!["a/b"]);
+        assert_eq!(n.to_path(false), "/a%2Fb");
+
+        let root = normalize_path("/");
+        assert!(root.segments.is_empty());
+        assert!(!root.trailing_slash);
+        assert_eq!(root.to_path(true), "/");
+
+        // Encoded dots are data, not navigation
+        assert_eq!(normalize_path("/a/%2E%2E").segments, vec!["a", ".."]);
+    }
+
+    #[test]
+    fn test_split_url_and_query() {
+        assert_eq!(split_url("/search?q=rust#top"), ("/search", Some("q=rust"), Some("top")));
+        assert_eq!(split_url("/a#frag?not-query"), ("/a", None, Some("frag?not-query")));
+        assert_eq!(split_url("/a"), ("/a", None, None));
+
+        let query = parse_query("q=rust+lang&tag=a&tag=b%26c&flag&=x");
+        assert_eq!(query["q"], vec!["rust lang"]);
+        assert_eq!(query["tag"], vec!["a", "b&c"]);
+        assert_eq!(query["flag"], vec![""]);
+        assert_eq!(query[""], vec!["x"]);
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("caf%C3%A9").as_deref(), Some("café"));
+        assert_eq!(percent_decode("100%").as_deref(), Some("100%"));
+        assert_eq!(percent_decode("%zz%4").as_deref(), Some("%zz%4"));
+        assert_eq!(percent_decode("%FF"), None);
+        assert_eq!(percent_encode_segment("café / ~x"), "caf%C3%A9%20%2F%20~x");
+    }
+}
@@ -204,6 +204,34 @@ impl RenderContext {
     pub fn get_interactive_nodes(&self) -> String {
         serde_json::to_string(&self.interactive_nodes).unwrap_or_else(|_| "[]".to_string())
     }
+
+    /// Collect the CSS classes used by a tree
+    pub fn collect_classes(&mut self, tree_json: &str) {
+        if let Ok(tree) = serde_json::from_str::<TreeNode>(tree_json) {
+            walk_tree_for_classes(&tree, &mut self.collected_classes);
+        }
+    }
+
+    /// Replace asset paths in a tree with data URIs from the asset manifest
+    pub fn resolve_assets(&self, tree_json: &str) -> String {
+        let Ok(mut tree) = serde_json::from_str::<serde_json::Value>(tree_json) else {
+            return tree_json.to_string();
+        };
+        resolve_assets_in_value(&mut tree, &self.asset_manifest);
+        serde_json::to_string(&tree).unwrap_or_else(|_| tree_json.to_string())
+    }
+
+    /// Critical CSS, @font-face rules and the rules of the collected classes
+    pub fn generate_css(&self) -> String {
+        let mut classes: Vec<String> = self.collected_classes.iter().cloned().collect();
+        classes.sort();
+        format!(
+            "{}\n{}\n{}",
+            self.css_manifest.critical,
+            self.font_manifest.font_face_css,
+            css_for_classes(&classes, &self.css_manifest)
+        )
+    }
 }
 
 /// Component tree node for walking
@@ -462,17 +490,21 @@ pub fn generate_css_for_classes(classes_json: &str, manifest_json: &str) -> Stri
         Err(_) => return String::new(),
     };
 
+    css_for_classes(&classes, &manifest)
+}
+
+fn css_for_classes(classes: &[String], manifest: &CSSManifest) -> String {
     let mut css = String::new();
     let mut media_rules: HashMap<String, Vec<String>> = HashMap::new();
 
-    for class in &classes {
+    for class in classes {
         if let Some(rules) = manifest.rules.get(class) {
             for rule in rules {
                 let rule_css = format!("{} {{ {} }}\n", rule.selector, rule.declarations);
                 if let Some(media) = &rule.media_query {
                     media_rules
                         .entry(media.clone())
-                        .or_insert_with(Vec::new)
+                        .or_default()
                         .push(rule_css);
                 } else {
                     css.push_str(&rule_css);
@@ -496,10 +528,7 @@ pub fn generate_css_for_classes(classes_json: &str, manifest_json: &str) -> Stri
 /// Generate minimal hydration script for interactive components
 #[wasm_bindgen]
 pub fn generate_hydration_script(interactive_nodes_json: &str, env_json: &str) -> String {
-    let nodes: Vec<InteractiveNode> = match serde_json::from_str(interactive_nodes_json) {
-        Ok(n) => n,
-        Err(_) => Vec::new(),
-    };
+    let nodes: Vec<InteractiveNode> = serde_json::from_str(interactive_nodes_json).unwrap_or_default();
 
     if nodes.is_empty() {
         return String::new();
@@ -523,6 +552,57 @@ pub fn generate_hydration_script(interactive_nodes_json: &str, env_json: &str) -
     script
 }
 
+/// Node type marking where a layout renders its children
+pub const LAYOUT_CHILDREN_TYPE: &str = "aeon:children";
+
+/// Wrap a page tree in its layout trees.
+///
+/// `layouts_json` is an array of serialized layout trees, outermost first
+/// (the order of `RouteMatch::layouts_json`). Each layout receives the
+/// already-wrapped tree in place of its first `{"type": "aeon:children"}`
+/// node, or as its last child if it has no such node.
+#[wasm_bindgen]
+pub fn wrap_in_layouts(page_json: &str, layouts_json: &str) -> String {
+    let page: serde_json::Value = match serde_json::from_str(page_json) {
+        Ok(p) => p,
+        Err(_) => return page_json.to_string(),
+    };
+    let layouts: Vec<serde_json::Value> = serde_json::from_str(layouts_json).unwrap_or_default();
+
+    let wrapped = layouts.into_iter().rev().fold(page, |inner, mut layout| {
+        if let Some(inner) = replace_children_slot(&mut layout, inner) {
+            if let Some(map) = layout.as_object_mut() {
+                let children = map
+                    .entry("children")
+                    .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+                if let Some(children) = children.as_array_mut() {
+                    children.push(inner);
+                }
+            }
+        }
+        layout
+    });
+    serde_json::to_string(&wrapped).unwrap_or_else(|_| page_json.to_string())
+}
+
+/// Put `inner` in place of the first children slot; hands it back if there is none
+fn replace_children_slot(node: &mut serde_json::Value, inner: serde_json::Value) -> Option<serde_json::Value> {
+    if node.get("type").and_then(|t| t.as_str()) == Some(LAYOUT_CHILDREN_TYPE) {
+        *node = inner;
+        return None;
+    }
+    let mut inner = inner;
+    if let Some(children) = node.get_mut("children").and_then(|c| c.as_array_mut()) {
+        for child in children.iter_mut() {
+            match replace_children_slot(child, inner) {
+                Some(unused) => inner = unused,
+                None => return None,
+            }
+        }
+    }
+    Some(inner)
+}
+
 /// Full page render: combines tree rendering with CSS, assets, and fonts
 #[wasm_bindgen]
 pub fn render_page(
@@ -579,6 +659,28 @@ pub fn render_page(
 </html>"#, title_escaped, desc_meta, full_css, html_content)
 }
 
+/// Full page render with the page tree wrapped in its layout chain
+#[wasm_bindgen]
+pub fn render_page_with_layouts(
+    tree_json: &str,
+    layouts_json: &str,
+    css_manifest_json: &str,
+    asset_manifest_json: &str,
+    font_manifest_json: &str,
+    title: &str,
+    description: &str,
+) -> String {
+    let wrapped = wrap_in_layouts(tree_json, layouts_json);
+    render_page(
+        &wrapped,
+        css_manifest_json,
+        asset_manifest_json,
+        font_manifest_json,
+        title,
+        description,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -626,4 +728,43 @@ mod tests {
         assert_eq!(to_kebab_case("fontSize"), "font-size");
         assert_eq!(to_kebab_case("color"), "color");
     }
+
+    #[test]
+    fn test_wrap_in_layouts() {
+        let page = r#"{"type": "p", "props": {}, "children": ["page"]}"#;
+        let layouts = r#"[
+            {"type": "main", "props": {"className": "root"}, "children": [
+                {"type": "nav", "props": {}, "children": []},
+                {"type": "aeon:children"}
+            ]},
+            {"type": "section", "props": {}, "children": [{"type": "h1", "props": {}, "children": ["Settings"]}]}
+        ]"#;
+
+        let html = render_tree_to_html(&wrap_in_layouts(page, layouts));
+        assert_eq!(
+            html,
+            "<main class=\"root\"><nav></nav><section><h1>Settings</h1><p>page</p></section></main>"
+        );
+
+        assert_eq!(wrap_in_layouts(page, "[]"), serde_json::to_string(&serde_json::from_str::<serde_json::Value>(page).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_render_context_uses_manifests() {
+        let css = r#"{"version": "1", "critical": "body{margin:0}", "rules": {
+            "p-4": [{"selector": ".p-4", "declarations": "padding: 1rem", "media_query": null}]
+        }}"#;
+        let assets = r#"{"version": "1", "assets": {"/logo.svg": {
+            "original_path": "/logo.svg", "data_uri": "data:image/svg+xml;base64,AA==", "size": 1, "format": "svg"
+        }}}"#;
+        let fonts = r#"{"version": "1", "fonts": {}, "font_face_css": "@font-face{}"}"#;
+        let mut context = RenderContext::new(css, assets, fonts).unwrap();
+
+        context.collect_classes(r#"{"type": "div", "props": {"className": "p-4 unknown"}}"#);
+        assert_eq!(context.generate_css(), "body{margin:0}\n@font-face{}\n.p-4 { padding: 1rem }\n");
+        assert_eq!(
+            context.resolve_assets(r#"{"type": "img", "props": {"src": "/logo.svg"}}"#),
+            r#"{"props":{"src":"data:image/svg+xml;base64,AA=="},"type":"img"}"#
+        );
+    }
 }
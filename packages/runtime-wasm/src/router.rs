@@ -3,133 +3,2143 @@
 //! Supports:
 //! - Static routes: /about, /blog
 //! - Dynamic segments: /blog/[slug]
+//! - Constrained segments: /users/[id:int], /files/[id:uuid],
+//!   /[lang:(en|de|fr)], /tags/[slug:/^[a-z-]+$/]
 //! - Catch-all segments: /api/[...path]
 //! - Optional catch-all: /docs/[[...slug]]
 //! - Route groups: (dashboard)/settings (ignored in URL)
+//! - Parallel route slots: /@modal/login (ignored in URL; matched alongside
+//!   the main route and returned in `RouteMatch::slots`)
+//! - Intercepting routes: /feed/(..)photo/[id] matches /photo/[id] when
+//!   navigating from a path under /feed; `(.)` is the same level, `(..)` one
+//!   level up (repeatable) and `(...)` the root
+//!
+//! Layouts are registered per pattern directory (route groups included), and
+//! every match carries the ordered chain of layouts from the root down.
+//!
+//! Request paths are normalized before matching (see `path.rs`): empty and
+//! dot segments are resolved and each segment is percent-decoded, so params
+//! and static comparisons see decoded values. The query string and fragment
+//! are split off first and returned on the `RouteMatch`.
+//!
+//! Routes may declare a `host` pattern (`app.example.com`, `[tenant].example.com`,
+//! `*.example.com`); they only match through `match_route_with_host` and
+//! rank above routes without a host.
+//!
+//! Routes may restrict their HTTP `methods`; `match_route_with_method`
+//! prefers such routes and reports "method not allowed" with the `Allow` list
+//! (`match_route` ignores methods).
+//!
+//! With locales configured (`set_locales`), a leading locale segment such as
+//! `/de/blog/x` is stripped before matching and reported as the match's
+//! `locale`; unprefixed paths get the default locale.
+//!
+//! Redirect and rewrite rules (see `rules.rs`) are applied by `evaluate`
+//! before routes are matched.
+//!
+//! `speculation_rules` emits prefetch and prerender rules for the route
+//! table (see `speculation.rs`).
+//!
+//! `to_bytes` snapshots a compiled router into a binary blob (see
+//! `snapshot.rs`) that `from_bytes` loads without re-parsing patterns.
+
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::path::{normalize_path, NormalizedPath, parse_query, percent_encode_segment, split_url};
+use crate::rules::{CompiledRule, RequestContext, RouteOutcome, RuleDefinition, RuleKind};
+use crate::snapshot::{self, Reader, SnapshotError, Writer};
+use crate::speculation::{url_pattern, Eagerness, SpeculationAction, SpeculationRules};
+use crate::template::{SeparatorEscape, SessionTemplate};
+use crate::{RouteDefinition, RouteMatch};
+
+/// Segment type for route pattern parsing
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Segment {
+    /// Static segment like "blog" or "about"
+    Static(String),
+    /// Dynamic segment like [slug] or [id:int]
+    Dynamic(String, Option<ParamConstraint>),
+    /// Catch-all segment like [...path]
+    CatchAll(String),
+    /// Optional catch-all like [[...slug]]
+    OptionalCatchAll(String),
+}
+
+/// Constraint on the value of a dynamic segment
+#[derive(Clone, Debug)]
+pub(crate) enum ParamConstraint {
+    /// `[id:int]` - optional `-` followed by ASCII digits
+    Int,
+    /// `[id:uuid]` - 8-4-4-4-12 hex digits
+    Uuid,
+    /// `[lang:(en|de|fr)]` - one of a fixed set of values
+    OneOf(Vec<String>),
+    /// `[slug:/^[a-z-]+$/]` - a regex that must match the whole segment
+    Pattern(regex_lite::Regex),
+}
+
+impl ParamConstraint {
+    fn parse(spec: &str) -> Result<Self, String> {
+        match spec {
+            "int" => return Ok(Self::Int),
+            "uuid" => return Ok(Self::Uuid),
+            _ => {}
+        }
+        if let Some(options) = spec.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            let options: Vec<String> = options.split('|').map(str::to_string).collect();
+            if options.iter().any(|o| o.is_empty() || !o.chars().all(is_valid_static_char)) {
+                return Err(format!("invalid option list {}", spec));
+            }
+            return Ok(Self::OneOf(options));
+        }
+        if let Some(source) = spec.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            return regex_lite::Regex::new(&format!("^(?:{})$", source))
+                .map(Self::Pattern)
+                .map_err(|e| format!("invalid regex {}: {}", spec, e));
+        }
+        Err(format!(
+            "unknown constraint \"{}\" (expected int, uuid, (a|b) or /regex/)",
+            spec
+        ))
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Self::Int => {
+                let digits = value.strip_prefix('-').unwrap_or(value);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            Self::Uuid => {
+                value.len() == 36
+                    && value.bytes().enumerate().all(|(i, b)| match i {
+                        8 | 13 | 18 | 23 => b == b'-',
+                        _ => b.is_ascii_hexdigit(),
+                    })
+            }
+            Self::OneOf(options) => options.iter().any(|o| o == value),
+            Self::Pattern(regex) => regex.is_match(value),
+        }
+    }
+
+    /// Whether every value accepted by `other` is accepted by `self`
+    fn accepts_all_of(&self, other: &ParamConstraint) -> bool {
+        match other {
+            Self::OneOf(options) => options.iter().all(|o| self.matches(o)),
+            _ => self == other,
+        }
+    }
+}
+
+impl std::fmt::Display for ParamConstraint {
+    /// The constraint in pattern syntax
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int => write!(f, "int"),
+            Self::Uuid => write!(f, "uuid"),
+            Self::OneOf(options) => write!(f, "({})", options.join("|")),
+            Self::Pattern(regex) => {
+                let source = regex.as_str();
+                let source = source
+                    .strip_prefix("^(?:")
+                    .and_then(|s| s.strip_suffix(")$"))
+                    .unwrap_or(source);
+                write!(f, "/{}/", source)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Segment {
+    /// The segment in pattern syntax
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Static(value) => write!(f, "{}", value),
+            Segment::Dynamic(name, None) => write!(f, "[{}]", name),
+            Segment::Dynamic(name, Some(constraint)) => write!(f, "[{}:{}]", name, constraint),
+            Segment::CatchAll(name) => write!(f, "[...{}]", name),
+            Segment::OptionalCatchAll(name) => write!(f, "[[...{}]]", name),
+        }
+    }
+}
+
+impl Segment {
+    /// Name of the param this segment binds, if any
+    pub(crate) fn param_name(&self) -> Option<&str> {
+        match self {
+            Segment::Static(_) => None,
+            Segment::Dynamic(name, _) | Segment::CatchAll(name) | Segment::OptionalCatchAll(name) => Some(name),
+        }
+    }
+}
+
+impl PartialEq for ParamConstraint {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Int, Self::Int) | (Self::Uuid, Self::Uuid) => true,
+            (Self::OneOf(a), Self::OneOf(b)) => a == b,
+            (Self::Pattern(a), Self::Pattern(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+/// Output of `parse_pattern`
+#[derive(Clone, Debug)]
+pub(crate) struct ParsedPattern {
+    /// URL segments (groups and slots removed, interceptors resolved)
+    pub segments: Vec<Segment>,
+    /// Parallel route slot from an `@name` directory
+    pub slot: Option<String>,
+    /// For intercepting routes, the URL segments of the directory declaring
+    /// the interceptor: navigations from paths under it are intercepted
+    pub intercept_from: Option<Vec<Segment>>,
+}
+
+/// Where an interceptor's target path starts, relative to its directory
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InterceptLevel {
+    /// `(.)` - the same level
+    Same,
+    /// `(..)`, `(..)(..)`, ... - that many levels up
+    Up(usize),
+    /// `(...)` - the root
+    Root,
+}
+
+/// One `.`-separated label of a route's host pattern
+#[derive(Clone, Debug, PartialEq)]
+enum HostLabel {
+    /// `app` - matched case-insensitively
+    Static(String),
+    /// `[tenant]` - any label, captured as a param
+    Param(String),
+    /// `*` - any label
+    Wildcard,
+}
+
+/// The set of routes a route is matched against
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum RouteTable {
+    /// Regular pages
+    Main,
+    /// Pages of one parallel route slot
+    Slot(String),
+    /// Intercepting routes (in any slot)
+    Intercept,
+}
+
+/// Parsed route pattern
+#[derive(Clone, Debug)]
+struct ParsedRoute {
+    segments: Vec<Segment>,
+    table: RouteTable,
+    slot: Option<String>,
+    intercept_from: Option<Vec<Segment>>,
+    /// Cached `route_specificity` of `segments`
+    specificity: usize,
+    /// Host pattern labels, if the route is restricted to matching hosts
+    host: Option<Vec<HostLabel>>,
+    /// Cached `host_specificity` of `host` (0 without a host)
+    host_specificity: usize,
+    /// Uppercase allowed methods (`None` allows any); `GET` implies `HEAD`
+    methods: Option<Vec<String>>,
+    /// Compiled `definition.session_id`
+    session_template: SessionTemplate,
+    /// Layout keys of every directory from the root down to the page, e.g.
+    /// `["/", "/(dashboard)", "/(dashboard)/settings"]`
+    directories: Vec<String>,
+    definition: RouteDefinition,
+}
+
+impl ParsedRoute {
+    /// Assemble a route, deriving its table and cached fields from the
+    /// segments, markers and host labels
+    fn new(
+        definition: RouteDefinition,
+        segments: Vec<Segment>,
+        slot: Option<String>,
+        intercept_from: Option<Vec<Segment>>,
+        host: Option<Vec<HostLabel>>,
+        methods: Option<Vec<String>>,
+        session_template: SessionTemplate,
+    ) -> Self {
+        let table = match (&intercept_from, &slot) {
+            (Some(_), _) => RouteTable::Intercept,
+            (None, Some(slot)) => RouteTable::Slot(slot.clone()),
+            (None, None) => RouteTable::Main,
+        };
+        Self {
+            specificity: route_specificity(&segments),
+            host_specificity: host.as_deref().map_or(0, host_specificity),
+            directories: pattern_directories(&definition.pattern()),
+            segments,
+            table,
+            slot,
+            intercept_from,
+            host,
+            methods,
+            session_template,
+            definition,
+        }
+    }
+
+    /// Whether a request with `method` may match (`None` matches any route)
+    fn allows(&self, method: Option<&str>) -> bool {
+        match (&self.methods, method) {
+            (Some(methods), Some(method)) => methods
+                .iter()
+                .any(|m| m == method || (m == "GET" && method == "HEAD")),
+            _ => true,
+        }
+    }
+
+    /// Whether every method `other` accepts is accepted here
+    fn allows_all_methods_of(&self, other: &ParsedRoute) -> bool {
+        match (&self.methods, &other.methods) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(_), Some(theirs)) => theirs.iter().all(|m| self.allows(Some(m))),
+        }
+    }
+
+    /// Whether both routes are matched against the same requests: same table
+    /// and slot, and for interceptors the same referrer prefix
+    fn competes_with(&self, other: &ParsedRoute) -> bool {
+        self.table == other.table
+            && self.slot == other.slot
+            && self.host == other.host
+            && match (&self.intercept_from, &other.intercept_from) {
+                (None, None) => true,
+                (Some(a), Some(b)) => same_matcher(a, b),
+                _ => false,
+            }
+    }
+}
+
+/// Segment trie node. Routes are stored by their index in `AeonRouter::routes`.
+#[derive(Clone, Debug, Default)]
+struct TrieNode {
+    /// Children keyed by static segment value
+    static_children: HashMap<String, TrieNode>,
+    /// Single child shared by every dynamic segment at this depth; constraints
+    /// are checked by `match_segments`
+    dynamic_child: Option<Box<TrieNode>>,
+    /// Routes ending in a catch-all at this depth (need at least one more segment)
+    catch_all: Vec<usize>,
+    /// Routes ending in an optional catch-all at this depth (zero or more segments)
+    optional_catch_all: Vec<usize>,
+    /// Routes whose pattern ends exactly at this node
+    terminal: Vec<usize>,
+}
+
+impl TrieNode {
+    /// Insert a route's segments. Validated patterns only have a catch-all last.
+    fn insert(&mut self, segments: &[Segment], route_idx: usize, case_sensitive: bool) {
+        let mut node = self;
+        for segment in segments {
+            node = match segment {
+                Segment::Static(value) => node
+                    .static_children
+                    .entry(static_key(value, case_sensitive))
+                    .or_default(),
+                Segment::Dynamic(..) => node.dynamic_child.get_or_insert_with(Default::default),
+                Segment::CatchAll(_) => {
+                    node.catch_all.push(route_idx);
+                    return;
+                }
+                Segment::OptionalCatchAll(_) => {
+                    node.optional_catch_all.push(route_idx);
+                    return;
+                }
+            };
+        }
+        node.terminal.push(route_idx);
+    }
+
+    /// Collect every route that could match `path_segments`
+    fn collect(&self, path_segments: &[&str], case_sensitive: bool, candidates: &mut Vec<usize>) {
+        candidates.extend_from_slice(&self.optional_catch_all);
+        let Some((head, rest)) = path_segments.split_first() else {
+            candidates.extend_from_slice(&self.terminal);
+            return;
+        };
+        candidates.extend_from_slice(&self.catch_all);
+        if let Some(child) = self.static_children.get(&static_key(head, case_sensitive)) {
+            child.collect(rest, case_sensitive, candidates);
+        }
+        if let Some(child) = &self.dynamic_child {
+            child.collect(rest, case_sensitive, candidates);
+        }
+    }
+}
+
+/// How `match_route` treats a trailing `/` on request paths
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrailingSlash {
+    /// `/about` and `/about/` both match; the normalized path has no trailing slash
+    Ignore,
+    /// Only `/about` matches
+    Never,
+    /// Only `/about/` matches; the normalized path keeps the trailing slash
+    Always,
+}
+
+/// Longest rewrite chain `evaluate` follows
+const MAX_REWRITES: usize = 16;
+
+/// A request URL prepared for matching
+struct Request {
+    /// Lowercase host without port (see `normalize_host`)
+    host: Option<String>,
+    /// Uppercase HTTP method; `None` matches routes regardless of methods
+    method: Option<String>,
+    path: NormalizedPath,
+    /// Whether normalized paths keep a trailing slash (see `TrailingSlash`)
+    trailing_slash: bool,
+    query: HashMap<String, Vec<String>>,
+    fragment: Option<String>,
+    /// Locale from the path prefix, or the default locale
+    locale: Option<String>,
+    /// Whether the first path segment is a locale prefix
+    locale_prefixed: bool,
+}
+
+impl Request {
+    /// Path segments to match against routes (without the locale prefix)
+    fn segments(&self) -> Vec<&str> {
+        let skip = usize::from(self.locale_prefixed);
+        self.path.segments.iter().skip(skip).map(String::as_str).collect()
+    }
+}
+
+/// The Aeon Router - matches URLs to routes
+///
+/// Routes are compiled into a segment trie. A lookup walks the trie to
+/// collect candidates and then picks the one with the highest precedence
+/// (see `precedence`): host, then path specificity, then registration order.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct AeonRouter {
+    /// Routes in registration order
+    routes: Vec<ParsedRoute>,
+    /// One trie per route table
+    tries: HashMap<RouteTable, TrieNode>,
+    /// Route index by `RouteDefinition::name`
+    names: HashMap<String, usize>,
+    /// Layout ID by directory key (see `directory_key`)
+    layouts: HashMap<String, String>,
+    /// Redirect and rewrite rules in evaluation order
+    rules: Vec<CompiledRule>,
+    /// Locales recognized as a leading path segment, as configured
+    locales: Vec<String>,
+    /// Locale of unprefixed paths; never used as a prefix by `url_for_locale`
+    default_locale: Option<String>,
+    session_escape: SeparatorEscape,
+    /// Compare static segments exactly (default) or case-insensitively
+    case_sensitive: bool,
+    trailing_slash: TrailingSlash,
+    /// Route table version, see `version`
+    version: u32,
+}
+
+#[wasm_bindgen]
+impl AeonRouter {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            tries: HashMap::new(),
+            names: HashMap::new(),
+            layouts: HashMap::new(),
+            rules: Vec::new(),
+            locales: Vec::new(),
+            default_locale: None,
+            session_escape: SeparatorEscape::default(),
+            version: 0,
+            case_sensitive: true,
+            trailing_slash: TrailingSlash::Ignore,
+        }
+    }
+
+    /// Match static segments case-insensitively when `false` (default `true`)
+    pub fn set_case_sensitive(&mut self, case_sensitive: bool) {
+        if self.case_sensitive != case_sensitive {
+            self.case_sensitive = case_sensitive;
+            self.rebuild_trie();
+        }
+    }
+
+    /// Set the trailing-slash policy (default `TrailingSlash::Ignore`)
+    pub fn set_trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
+    /// Configure i18n routing: `locales` like `["en", "de", "fr-CA"]` and the
+    /// locale of unprefixed paths (added to `locales` if missing)
+    pub fn set_locales(&mut self, locales: Vec<String>, default_locale: &str) {
+        self.locales = locales;
+        if !self.locales.iter().any(|l| l.eq_ignore_ascii_case(default_locale)) {
+            self.locales.push(default_locale.to_string());
+        }
+        self.default_locale = self.find_locale(default_locale);
+    }
+
+    /// Pick the best configured locale for an `Accept-Language` header.
+    ///
+    /// Languages are tried by descending `q`; a language matches a locale
+    /// exactly or by primary subtag (`de-AT` matches `de`, `fr` matches
+    /// `fr-CA`). Falls back to the default locale.
+    pub fn negotiate_locale(&self, accept_language: &str) -> Option<String> {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let q = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (!tag.is_empty() && q > 0.0).then_some((tag, q))
+            })
+            .collect();
+        // Stable sort keeps header order for equal weights
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let primary = |tag: &str| tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+        ranges
+            .iter()
+            .find_map(|(tag, _)| {
+                self.find_locale(tag).or_else(|| {
+                    self.locales
+                        .iter()
+                        .find(|locale| primary(locale) == primary(tag))
+                        .cloned()
+                })
+            })
+            .or_else(|| self.default_locale.clone())
+    }
+
+    /// Escape `separators` (e.g. `"/:"`) in values substituted into session
+    /// IDs: replace each with `replacement`, or percent-encode it if `None`.
+    /// Nothing is escaped by default.
+    pub fn set_session_escape(&mut self, separators: &str, replacement: Option<String>) {
+        self.session_escape = SeparatorEscape {
+            separators: separators.to_string(),
+            replacement,
+        };
+    }
+
+    /// Add a route to the router
+    ///
+    /// Fails with a `RouteError` if the pattern or session ID template is
+    /// invalid or the route's name is already taken.
+    pub fn add_route(&mut self, definition: RouteDefinition) -> Result<(), RouteError> {
+        let route = self.compile_route(definition, None)?;
+        let route_idx = self.routes.len();
+        if let Some(name) = route.definition.name() {
+            self.names.insert(name, route_idx);
+        }
+        self.tries
+            .entry(route.table.clone())
+            .or_default()
+            .insert(&route.segments, route_idx, self.case_sensitive);
+        self.routes.push(route);
+        self.version += 1;
+        Ok(())
+    }
+
+    /// Remove a route by pattern, name or component ID (as in `url_for`).
+    /// Returns the new version.
+    pub fn remove_route(&mut self, route: &str) -> Result<u32, RouteError> {
+        let route_idx = self.route_index(route).ok_or_else(|| unknown_route(route))?;
+        self.routes.remove(route_idx);
+        self.reindex();
+        self.version += 1;
+        Ok(self.version)
+    }
+
+    /// Replace a route (found by pattern, name or component ID) in place,
+    /// keeping its registration order. Returns the new version.
+    pub fn replace_route(&mut self, route: &str, definition: RouteDefinition) -> Result<u32, RouteError> {
+        let route_idx = self.route_index(route).ok_or_else(|| unknown_route(route))?;
+        self.routes[route_idx] = self.compile_route(definition, Some(route_idx))?;
+        self.reindex();
+        self.version += 1;
+        Ok(self.version)
+    }
+
+    /// Remove and add routes in one step: either every change applies or
+    /// none does.
+    ///
+    /// `removed_json` is an array of patterns, names or component IDs,
+    /// removed first; `added_json` is an array of route definitions as in
+    /// the manifest. Returns the new version (bumped once).
+    pub fn apply_route_diff(&mut self, added_json: &str, removed_json: &str) -> Result<u32, RouteError> {
+        let parse_error = |what: &str, e: serde_json::Error| {
+            RouteError::new("", "", RouteErrorKind::InvalidRouteDiff, &format!("{} must be a JSON array: {}", what, e))
+        };
+        let added: Vec<RouteDefinition> = serde_json::from_str(added_json).map_err(|e| parse_error("added routes", e))?;
+        let removed: Vec<String> = serde_json::from_str(removed_json).map_err(|e| parse_error("removed routes", e))?;
+
+        let mut next = self.clone();
+        for route in &removed {
+            let route_idx = next.route_index(route).ok_or_else(|| unknown_route(route))?;
+            next.routes.remove(route_idx);
+            next.reindex();
+        }
+        for definition in added {
+            next.add_route(definition)?;
+        }
+        next.version = self.version + 1;
+        *self = next;
+        Ok(self.version)
+    }
+
+    /// Bumped by every change to the route table
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Register the layout that wraps every route under `directory`.
+    ///
+    /// `directory` uses pattern syntax and keeps route groups, e.g. `/`,
+    /// `/(dashboard)` or `/(dashboard)/settings`.
+    pub fn add_layout(&mut self, directory: &str, layout_id: &str) {
+        self.layouts.insert(directory_key(directory), layout_id.to_string());
+    }
+
+    /// Match a URL path (optionally with `?query` and `#fragment`) to a route
+    ///
+    /// The best match of every parallel route slot is returned in the
+    /// match's `slots`. Routes that accept any method win over routes with
+    /// a method list; use `match_route_with_method` to route by method.
+    pub fn match_route(&self, path: &str) -> Option<RouteMatch> {
+        self.match_url(None, path, None)
+    }
+
+    /// Match a request by method and path, preferring routes that list the
+    /// method over routes that allow any method.
+    ///
+    /// Reports `MethodNotAllowed` with the `Allow` list when routes match
+    /// the path but none allows the method.
+    pub fn match_route_with_method(&self, method: &str, path: &str) -> MethodMatch {
+        let Some(mut request) = self.prepare_request(None, path) else {
+            return MethodMatch::not_found();
+        };
+        request.method = Some(method.to_ascii_uppercase());
+        if let Some(route_match) = self.match_request(&request, None) {
+            return MethodMatch {
+                status: MethodMatchStatus::Matched,
+                route_match: Some(route_match),
+                allow: Vec::new(),
+            };
+        }
+        let allow = self.allowed_methods(&request);
+        if allow.is_empty() {
+            return MethodMatch::not_found();
+        }
+        MethodMatch {
+            status: MethodMatchStatus::MethodNotAllowed,
+            route_match: None,
+            allow,
+        }
+    }
+
+    /// Match a request by host and path. Routes with a `host` pattern only
+    /// match here; their host params are merged into the path params.
+    ///
+    /// `host` may include a port, which is ignored.
+    pub fn match_route_with_host(&self, host: &str, path: &str) -> Option<RouteMatch> {
+        self.match_url(Some(host), path, None)
+    }
+
+    /// Match a client-side navigation from `referrer` to `path`.
+    ///
+    /// Like `match_route`, plus `intercepted`: the intercepting route that
+    /// takes over this navigation, if one is declared under `referrer`.
+    pub fn match_route_from(&self, path: &str, referrer: &str) -> Option<RouteMatch> {
+        self.match_url(None, path, Some(referrer))
+    }
+
+    /// Get all registered routes in precedence order (for debugging)
+    pub fn get_routes_json(&self) -> String {
+        let patterns: Vec<String> = self
+            .ordered_routes()
+            .into_iter()
+            .map(|idx| self.routes[idx].definition.pattern())
+            .collect();
+        serde_json::to_string(&patterns).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Every route matching a path, in precedence order, as a JSON array of
+    /// matches (the first one is what `match_route` returns)
+    pub fn match_all(&self, path: &str) -> String {
+        let matches: Vec<RouteMatch> = match self.prepare_request(None, path) {
+            None => Vec::new(),
+            Some(request) => {
+                let path_segments = request.segments();
+                self.candidates(&RouteTable::Main, &path_segments)
+                    .into_iter()
+                    .filter_map(|idx| {
+                        self.match_at(idx, &request, &path_segments)
+                            .map(|params| self.build_match(idx, params, &request))
+                    })
+                    .collect()
+            }
+        };
+        serde_json::to_string(&matches).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Why each route does or does not match a path, as JSON:
+    ///
+    /// `{ normalized_path, segments, locale, rejected, matched, routes: [{
+    /// pattern, matched, mismatch?: { reason, segment_index, expected, actual } }] }`
+    /// with routes in precedence order. Slot and intercepting routes are not listed.
+    pub fn explain(&self, path: &str) -> String {
+        let request = self.parse_request(None, path);
+        let rejected = (!self.accepts_trailing_slash(&request.path))
+            .then(|| format!("the {:?} trailing-slash policy rejects this path", self.trailing_slash));
+        let path_segments = request.segments();
+
+        let routes: Vec<RouteExplanation> = self
+            .ordered_routes()
+            .into_iter()
+            .map(|idx| &self.routes[idx])
+            .filter(|route| route.table == RouteTable::Main)
+            .map(|route| {
+                let mismatch = if route.host.is_some() {
+                    Some(SegmentMismatch {
+                        reason: MismatchReason::HostRequired,
+                        segment_index: 0,
+                        expected: route.definition.host(),
+                        actual: None,
+                    })
+                } else {
+                    explain_segments(&route.segments, &path_segments, self.case_sensitive).err()
+                };
+                RouteExplanation {
+                    pattern: route.definition.pattern(),
+                    matched: mismatch.is_none(),
+                    mismatch,
+                }
+            })
+            .collect();
+
+        let explanation = MatchExplanation {
+            normalized_path: request.path.to_path(request.trailing_slash),
+            segments: path_segments.iter().map(|s| s.to_string()).collect(),
+            locale: request.locale.clone(),
+            matched: match rejected {
+                Some(_) => None,
+                None => routes.iter().find(|r| r.matched).map(|r| r.pattern.clone()),
+            },
+            rejected,
+            routes,
+        };
+        serde_json::to_string(&explanation).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    /// Check if a route exists for the given path
+    pub fn has_route(&self, path: &str) -> bool {
+        self.match_route(path).is_some()
+    }
+
+    /// Look up a route definition by its name
+    pub fn get_by_name(&self, name: &str) -> Option<RouteDefinition> {
+        self.names.get(name).map(|&idx| self.routes[idx].definition.clone())
+    }
+
+    /// Build a concrete URL for a registered route.
+    ///
+    /// `route` is a route pattern, a route name or a component ID, tried in
+    /// that order.
+    /// `params_json` is an object of param values: strings or numbers for
+    /// dynamic segments, arrays (or `/`-separated strings) for catch-alls.
+    /// Values are percent-encoded; an empty or missing optional catch-all is
+    /// omitted. Missing, unknown or constraint-violating params are errors.
+    pub fn url_for(&self, route: &str, params_json: &str) -> Result<String, RouteError> {
+        let parsed = self.find_route(route).ok_or_else(|| unknown_route(route))?;
+        let pattern = parsed.definition.pattern();
+
+        let params: serde_json::Map<String, serde_json::Value> = if params_json.trim().is_empty() {
+            serde_json::Map::new()
+        } else {
+            serde_json::from_str(params_json).map_err(|e| {
+                RouteError::new(
+                    &pattern,
+                    "",
+                    RouteErrorKind::InvalidParamValue,
+                    &format!("params must be a JSON object: {}", e),
+                )
+            })?
+        };
+
+        let mut used = HashSet::new();
+        let mut segments: Vec<String> = Vec::new();
+        for segment in &parsed.segments {
+            let error = |kind: RouteErrorKind, reason: &str| RouteError::new(&pattern, &segment.to_string(), kind, reason);
+            match segment {
+                Segment::Static(value) => segments.push(percent_encode_segment(value)),
+                Segment::Dynamic(name, constraint) => {
+                    used.insert(name.as_str());
+                    let value = match params.get(name) {
+                        None | Some(serde_json::Value::Null) => {
+                            return Err(error(RouteErrorKind::MissingParam, &format!("missing param \"{}\"", name)));
+                        }
+                        Some(value) => scalar_param(value).ok_or_else(|| {
+                            error(RouteErrorKind::InvalidParamValue, &format!("param \"{}\" must be a string or number", name))
+                        })?,
+                    };
+                    if value.is_empty() || constraint.as_ref().is_some_and(|c| !c.matches(&value)) {
+                        return Err(error(
+                            RouteErrorKind::InvalidParamValue,
+                            &format!("value {:?} is not valid for param \"{}\"", value, name),
+                        ));
+                    }
+                    segments.push(percent_encode_segment(&value));
+                }
+                Segment::CatchAll(name) | Segment::OptionalCatchAll(name) => {
+                    used.insert(name.as_str());
+                    let optional = matches!(segment, Segment::OptionalCatchAll(_));
+                    let values = match params.get(name) {
+                        None | Some(serde_json::Value::Null) => Vec::new(),
+                        Some(value) => catch_all_param(value).ok_or_else(|| {
+                            error(
+                                RouteErrorKind::InvalidParamValue,
+                                &format!("param \"{}\" must be an array of strings or a string", name),
+                            )
+                        })?,
+                    };
+                    if values.is_empty() && !optional {
+                        return Err(error(RouteErrorKind::MissingParam, &format!("missing param \"{}\"", name)));
+                    }
+                    segments.extend(values.iter().map(|v| percent_encode_segment(v)));
+                }
+            }
+        }
+
+        if let Some(extra) = params.keys().find(|key| !used.contains(key.as_str())) {
+            return Err(RouteError::new(
+                &pattern,
+                "",
+                RouteErrorKind::UnexpectedParam,
+                &format!("route has no param \"{}\"", extra),
+            ));
+        }
+
+        let mut url = format!("/{}", segments.join("/"));
+        if self.trailing_slash == TrailingSlash::Always && !segments.is_empty() {
+            url.push('/');
+        }
+        Ok(url)
+    }
+
+    /// `url_for` with a locale prefix: `/de/blog/x` for `de`, no prefix for
+    /// the default locale
+    pub fn url_for_locale(&self, route: &str, params_json: &str, locale: &str) -> Result<String, RouteError> {
+        let canonical = self.find_locale(locale).ok_or_else(|| {
+            RouteError::new(route, "", RouteErrorKind::UnknownLocale, &format!("locale \"{}\" is not configured", locale))
+        })?;
+        Ok(self.localize_url(self.url_for(route, params_json)?, &canonical))
+    }
+
+    /// Speculation Rules JSON with prefetch and prerender rules for every
+    /// route (see `speculation.rs`)
+    pub fn speculation_rules(&self, eagerness: Eagerness) -> String {
+        let mut rules = SpeculationRules::default();
+        for route in &self.routes {
+            if route.table != RouteTable::Main || route.host.is_some() || !route.allows(Some("GET")) {
+                continue;
+            }
+            let dynamic = route.segments.iter().any(|s| s.param_name().is_some());
+            let pattern = || url_pattern(&route.segments, &self.locales, self.trailing_slash);
+            match SpeculationAction::for_route(route.definition.metadata.as_ref(), dynamic) {
+                None => rules.exclude(pattern()),
+                Some(action) if dynamic => rules.add_pattern(action, pattern()),
+                Some(action) => {
+                    let segments = route.segments.iter().map(ToString::to_string).collect();
+                    let url = NormalizedPath { segments, trailing_slash: false }
+                        .to_path(self.trailing_slash == TrailingSlash::Always);
+                    let localized = self.locales.iter().map(|locale| self.localize_url(url.clone(), locale));
+                    let mut urls = vec![url.clone()];
+                    urls.extend(localized);
+                    rules.add_urls(action, urls);
+                }
+            }
+        }
+        rules.to_json(eagerness).to_string()
+    }
+
+    /// Ambiguous and shadowed route pairs as JSON (for CI checks)
+    ///
+    /// `[{ kind: "Ambiguous" | "Shadowed", winner, shadowed }]`, where
+    /// `winner` and `shadowed` are route patterns.
+    pub fn conflicts(&self) -> String {
+        serde_json::to_string(&self.route_conflicts()).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Build a router from the build's route manifest.
+    ///
+    /// Fails with the `ManifestReport` if any entry is invalid; its `errors`
+    /// carry the offending segment and reason of each rejected entry.
+    pub fn from_manifest_json(json: &str) -> Result<AeonRouter, ManifestReport> {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(json);
+        if report.is_ok() {
+            Ok(router)
+        } else {
+            Err(report)
+        }
+    }
+
+    /// Snapshot the compiled router (routes, layouts, rules and settings)
+    /// for `from_bytes`, e.g. at build time
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bool(self.case_sensitive);
+        w.u8(match self.trailing_slash {
+            TrailingSlash::Ignore => 0,
+            TrailingSlash::Never => 1,
+            TrailingSlash::Always => 2,
+        });
+        w.strs(&self.locales);
+        w.opt_str(self.default_locale.as_deref());
+        w.str(&self.session_escape.separators);
+        w.opt_str(self.session_escape.replacement.as_deref());
+        w.varint(u64::from(self.version));
+
+        let mut layouts: Vec<_> = self.layouts.iter().collect();
+        layouts.sort();
+        w.usize(layouts.len());
+        for (directory, layout_id) in layouts {
+            w.str(directory);
+            w.str(layout_id);
+        }
+        w.usize(self.routes.len());
+        for route in &self.routes {
+            write_route(&mut w, route);
+        }
+        w.usize(self.rules.len());
+        for rule in &self.rules {
+            rule.write(&mut w);
+        }
+        snapshot::seal(&w.bytes)
+    }
+
+    /// Load a router written by `to_bytes`.
+    ///
+    /// Fails with `InvalidSnapshot` if the blob is corrupted or truncated and
+    /// with `UnsupportedSnapshot` if it was written with another format version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<AeonRouter, RouteError> {
+        let payload = snapshot::open(bytes).map_err(snapshot_error)?;
+        Self::read_snapshot(&mut Reader::new(payload)).map_err(snapshot_error)
+    }
+
+    /// Load every route from a manifest in a single call.
+    ///
+    /// Accepts either `{ "version": ..., "routes": [...], "layouts": {...} }`
+    /// or a bare array of route definitions (snake_case or camelCase keys).
+    /// `layouts` maps directories to layout IDs as in `add_layout`. Valid
+    /// entries are added; invalid ones are skipped and listed in the report.
+    pub fn load_manifest(&mut self, json: &str) -> ManifestReport {
+        let mut report = ManifestReport::default();
+
+        let entries = match parse_manifest_entries(json) {
+            Ok((entries, layouts)) => {
+                for (directory, layout_id) in layouts {
+                    self.add_layout(&directory, &layout_id);
+                }
+                entries
+            }
+            Err(message) => {
+                report.errors.push(ManifestEntryError {
+                    index: None,
+                    pattern: None,
+                    message,
+                    kind: None,
+                    segment: None,
+                });
+                return report;
+            }
+        };
+
+        let mut seen: HashSet<String> = self.routes.iter().map(|r| route_key(&r.definition)).collect();
+        self.routes.reserve(entries.len());
+
+        for (index, entry) in entries.into_iter().enumerate() {
+            let pattern = entry.get("pattern").and_then(|p| p.as_str()).map(str::to_string);
+            let definition = match serde_json::from_value::<RouteDefinition>(entry) {
+                Ok(definition) => definition,
+                Err(e) => {
+                    report.errors.push(ManifestEntryError {
+                        index: Some(index),
+                        pattern,
+                        message: format!("Invalid route entry: {}", e),
+                        kind: None,
+                        segment: None,
+                    });
+                    continue;
+                }
+            };
+            if let Err(message) = validate_manifest_entry(&definition, &seen) {
+                report.errors.push(ManifestEntryError {
+                    index: Some(index),
+                    pattern,
+                    message,
+                    kind: None,
+                    segment: None,
+                });
+                continue;
+            }
+            let key = route_key(&definition);
+            if let Err(error) = self.add_route(definition) {
+                report.errors.push(ManifestEntryError {
+                    index: Some(index),
+                    pattern,
+                    message: error.to_string(),
+                    kind: Some(error.kind),
+                    segment: Some(error.segment),
+                });
+                continue;
+            }
+            seen.insert(key);
+            report.loaded += 1;
+        }
+        report
+    }
+
+    /// Add a redirect or rewrite rule; rules apply in the order they are added
+    pub fn add_rule(&mut self, rule: RuleDefinition) -> Result<(), RouteError> {
+        self.rules.push(CompiledRule::compile(rule)?);
+        Ok(())
+    }
+
+    /// Load an array of rule definitions:
+    /// `[{ source, destination, kind: "redirect" | "rewrite", permanent?, has? }]`
+    /// where `has` is `[{ type: "header" | "cookie", key, value? }]`.
+    /// Invalid rules are skipped and listed in the report.
+    pub fn load_rules(&mut self, json: &str) -> ManifestReport {
+        let mut report = ManifestReport::default();
+        let entries: Vec<serde_json::Value> = match serde_json::from_str(json) {
+            Ok(entries) => entries,
+            Err(e) => {
+                report.errors.push(ManifestEntryError {
+                    index: None,
+                    pattern: None,
+                    message: format!("Rules must be a JSON array: {}", e),
+                    kind: None,
+                    segment: None,
+                });
+                return report;
+            }
+        };
+        for (index, entry) in entries.into_iter().enumerate() {
+            let source = entry.get("source").and_then(|p| p.as_str()).map(str::to_string);
+            let result = serde_json::from_value::<RuleDefinition>(entry)
+                .map_err(|e| (format!("Invalid rule: {}", e), None))
+                .and_then(|rule| {
+                    self.add_rule(rule)
+                        .map_err(|error| (error.to_string(), Some((error.kind, error.segment))))
+                });
+            match result {
+                Ok(()) => report.loaded += 1,
+                Err((message, error)) => report.errors.push(ManifestEntryError {
+                    index: Some(index),
+                    pattern: source,
+                    message,
+                    kind: error.as_ref().map(|(kind, _)| *kind),
+                    segment: error.map(|(_, segment)| segment),
+                }),
+            }
+        }
+        report
+    }
+
+    /// Apply rules to a request URL, then match the resulting path.
+    ///
+    /// `request_json` is `{ "headers": {...}, "cookies": {...} }` (may be
+    /// empty) and is only used by rule conditions. The first redirect wins;
+    /// rewrites are re-evaluated against the rules until none applies, and
+    /// fail with `RewriteLoop` if a URL repeats or the chain gets too long.
+    pub fn evaluate(&self, url: &str, request_json: &str) -> Result<RouteOutcome, RouteError> {
+        let request = RequestContext::from_json(request_json)
+            .map_err(|reason| RouteError::new(url, "", RouteErrorKind::InvalidRequest, &reason))?;
+        let mut current = url.to_string();
+        let mut visited = HashSet::new();
+
+        while let Some((rule, destination)) = self.apply_rules(&current, &request) {
+            if rule.kind() == RuleKind::Redirect {
+                return Ok(RouteOutcome::redirect(destination, rule.status(), current));
+            }
+            visited.insert(current);
+            if visited.contains(&destination) || visited.len() >= MAX_REWRITES {
+                return Err(RouteError::new(
+                    url,
+                    "",
+                    RouteErrorKind::RewriteLoop,
+                    &format!("rewrite to {} loops or exceeds {} rewrites", destination, MAX_REWRITES),
+                ));
+            }
+            current = destination;
+        }
+
+        let rewritten = !visited.is_empty();
+        Ok(RouteOutcome::matched(current.clone(), self.match_route(&current), rewritten))
+    }
+}
+
+impl AeonRouter {
+    /// Every pair of routes where the lower-precedence route can never match
+    /// because the higher-precedence one accepts all of its paths.
+    pub fn route_conflicts(&self) -> Vec<RouteConflict> {
+        // A method-specific route only loses to a route that accepts all of
+        // its methods, so compare in the order method-aware matching uses
+        let mut order = self.ordered_routes();
+        order.sort_unstable_by_key(|&idx| self.method_precedence(idx));
+        let mut conflicts = Vec::new();
+        for (i, &winner_idx) in order.iter().enumerate() {
+            let winner = &self.routes[winner_idx];
+            for &loser_idx in &order[i + 1..] {
+                let loser = &self.routes[loser_idx];
+                if !winner.competes_with(loser)
+                    || !winner.allows_all_methods_of(loser)
+                    || !covers(&winner.segments, &loser.segments)
+                {
+                    continue;
+                }
+                let kind = if same_matcher(&winner.segments, &loser.segments) {
+                    ConflictKind::Ambiguous
+                } else {
+                    ConflictKind::Shadowed
+                };
+                conflicts.push(RouteConflict {
+                    kind,
+                    winner: winner.definition.pattern(),
+                    shadowed: loser.definition.pattern(),
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Layouts wrapping a route, outermost first: registered directory
+    /// layouts, then the route's own `layout` if it is not already included
+    fn layout_chain(&self, parsed: &ParsedRoute) -> Vec<String> {
+        let mut chain: Vec<String> = parsed
+            .directories
+            .iter()
+            .filter_map(|dir| self.layouts.get(dir).cloned())
+            .collect();
+        if let Some(layout) = parsed.definition.layout() {
+            if !chain.contains(&layout) {
+                chain.push(layout);
+            }
+        }
+        chain
+    }
+
+    /// Look up a route by pattern, name, then component ID (highest precedence wins)
+    fn find_route(&self, key: &str) -> Option<&ParsedRoute> {
+        self.route_index(key).map(|idx| &self.routes[idx])
+    }
+
+    /// Index of the route `find_route` returns
+    fn route_index(&self, key: &str) -> Option<usize> {
+        let ordered = self.ordered_routes();
+        ordered
+            .iter()
+            .copied()
+            .find(|&idx| self.routes[idx].definition.pattern() == key)
+            .or_else(|| self.names.get(key).copied())
+            .or_else(|| ordered.into_iter().find(|&idx| self.routes[idx].definition.component_id() == key))
+    }
+
+    /// Validate and compile a definition. `replacing` is the index of the
+    /// route it replaces, whose name may be reused.
+    fn compile_route(&self, definition: RouteDefinition, replacing: Option<usize>) -> Result<ParsedRoute, RouteError> {
+        let parsed = parse_pattern(&definition.pattern())?;
+        let host = definition
+            .host()
+            .map(|host| parse_host(&definition.pattern(), &host, &parsed.segments))
+            .transpose()?;
+        let methods = definition
+            .methods()
+            .map(|methods| parse_methods(&definition.pattern(), &methods))
+            .transpose()?;
+        let session_template = compile_session_template(&definition, &parsed.segments, host.as_deref())?;
+        if let Some(name) = definition.name() {
+            if self.names.get(&name).is_some_and(|&idx| Some(idx) != replacing) {
+                return Err(RouteError::new(
+                    &definition.pattern(),
+                    "",
+                    RouteErrorKind::DuplicateRouteName,
+                    &format!("route name \"{}\" is already registered", name),
+                ));
+            }
+        }
+        Ok(ParsedRoute::new(
+            definition,
+            parsed.segments,
+            parsed.slot,
+            parsed.intercept_from,
+            host,
+            methods,
+            session_template,
+        ))
+    }
+
+    fn match_url(&self, host: Option<&str>, url: &str, referrer: Option<&str>) -> Option<RouteMatch> {
+        self.match_request(&self.prepare_request(host, url)?, referrer)
+    }
+
+    fn match_request(&self, request: &Request, referrer: Option<&str>) -> Option<RouteMatch> {
+        let path_segments = request.segments();
+        let (route_idx, params) = self.find(&RouteTable::Main, request, &path_segments)?;
+        let mut route_match = self.build_match(route_idx, params, request);
+
+        for table in self.tries.keys() {
+            let RouteTable::Slot(slot) = table else { continue };
+            if let Some((idx, params)) = self.find(table, request, &path_segments) {
+                route_match.slots.insert(slot.clone(), self.build_match(idx, params, request));
+            }
+        }
+
+        if let Some(referrer) = referrer {
+            let from = normalize_path(split_url(referrer).0);
+            let from_segments: Vec<&str> = from.segments.iter().map(String::as_str).collect();
+            // The interceptor declared deepest under the referrer wins
+            let intercepted = self
+                .candidates(&RouteTable::Intercept, &path_segments)
+                .into_iter()
+                .filter_map(|idx| {
+                    let route = &self.routes[idx];
+                    let prefix = route.intercept_from.as_ref()?;
+                    if !matches_prefix(prefix, &from_segments, self.case_sensitive) {
+                        return None;
+                    }
+                    let params = self.match_at(idx, request, &path_segments)?;
+                    Some((idx, params, std::cmp::Reverse(route_specificity(prefix))))
+                })
+                .min_by_key(|(_, _, depth)| *depth)
+                .map(|(idx, params, _)| (idx, params));
+            if let Some((idx, params)) = intercepted {
+                route_match.intercepted = Some(Box::new(self.build_match(idx, params, request)));
+            }
+        }
+        Some(route_match)
+    }
+
+    /// Prefix a URL with a locale; the default locale is never a prefix
+    fn localize_url(&self, url: String, locale: &str) -> String {
+        if self.default_locale.as_deref() == Some(locale) {
+            return url;
+        }
+        let prefix = format!("/{}", percent_encode_segment(locale));
+        if url == "/" {
+            match self.trailing_slash {
+                TrailingSlash::Always => format!("{}/", prefix),
+                _ => prefix,
+            }
+        } else {
+            format!("{}{}", prefix, url)
+        }
+    }
+
+    /// The configured spelling of a locale (compared case-insensitively)
+    fn find_locale(&self, value: &str) -> Option<String> {
+        self.locales.iter().find(|l| l.eq_ignore_ascii_case(value)).cloned()
+    }
+
+    /// The first rule that applies to a URL, with its destination
+    fn apply_rules(&self, url: &str, request: &RequestContext) -> Option<(&CompiledRule, String)> {
+        let (path, query, _) = split_url(url);
+        let normalized = normalize_path(path);
+        let path_segments: Vec<&str> = normalized.segments.iter().map(String::as_str).collect();
+        let locale = path_segments.first().and_then(|first| self.find_locale(first)).or_else(|| self.default_locale.clone());
+        self.rules.iter().find_map(|rule| {
+            rule.apply(&path_segments, query, locale.as_deref(), request, self.case_sensitive)
+                .map(|destination| (rule, destination))
+        })
+    }
+
+    /// Split and normalize a request URL; `None` if the trailing-slash
+    /// policy rejects it
+    fn prepare_request(&self, host: Option<&str>, url: &str) -> Option<Request> {
+        let request = self.parse_request(host, url);
+        self.accepts_trailing_slash(&request.path).then_some(request)
+    }
+
+    /// Split and normalize a request URL without applying the trailing-slash policy
+    fn parse_request(&self, host: Option<&str>, url: &str) -> Request {
+        let (path, query, fragment) = split_url(url);
+        let path = normalize_path(path);
+        let prefix = path.segments.first().and_then(|first| self.find_locale(first));
+        Request {
+            host: host.map(normalize_host),
+            method: None,
+            locale_prefixed: prefix.is_some(),
+            locale: prefix.or_else(|| self.default_locale.clone()),
+            path,
+            trailing_slash: self.trailing_slash == TrailingSlash::Always,
+            query: query.map(parse_query).unwrap_or_default(),
+            fragment: fragment.map(str::to_string),
+        }
+    }
+
+    fn accepts_trailing_slash(&self, path: &NormalizedPath) -> bool {
+        match self.trailing_slash {
+            TrailingSlash::Ignore => true,
+            TrailingSlash::Never => !path.trailing_slash,
+            TrailingSlash::Always => path.trailing_slash || path.segments.is_empty(),
+        }
+    }
+
+    /// The highest-precedence route of `table` matching the path, with its params
+    fn find(
+        &self,
+        table: &RouteTable,
+        request: &Request,
+        path_segments: &[&str],
+    ) -> Option<(usize, HashMap<String, String>)> {
+        let mut candidates = self.candidates(table, path_segments);
+        if request.method.is_some() {
+            candidates.sort_unstable_by_key(|&idx| self.method_precedence(idx));
+        }
+        candidates
+            .into_iter()
+            .filter(|&route_idx| self.routes[route_idx].allows(request.method.as_deref()))
+            .find_map(|route_idx| self.match_at(route_idx, request, path_segments).map(|params| (route_idx, params)))
+    }
+
+    /// Methods allowed by every main route matching the request's host and
+    /// path, sorted (empty if no route matches)
+    fn allowed_methods(&self, request: &Request) -> Vec<String> {
+        let path_segments = request.segments();
+        let mut allow = std::collections::BTreeSet::new();
+        for route_idx in self.candidates(&RouteTable::Main, &path_segments) {
+            if self.match_at(route_idx, request, &path_segments).is_none() {
+                continue;
+            }
+            for method in self.routes[route_idx].methods.iter().flatten() {
+                if method == "GET" {
+                    allow.insert("HEAD".to_string());
+                }
+                allow.insert(method.clone());
+            }
+        }
+        allow.into_iter().collect()
+    }
+
+    /// Host and path params if a route matches the request
+    fn match_at(&self, route_idx: usize, request: &Request, path_segments: &[&str]) -> Option<HashMap<String, String>> {
+        let route = &self.routes[route_idx];
+        let mut params = match (&route.host, &request.host) {
+            (None, _) => HashMap::new(),
+            (Some(labels), Some(host)) => match_host(labels, host)?,
+            (Some(_), None) => return None,
+        };
+        params.extend(match_segments(&route.segments, path_segments, self.case_sensitive)?);
+        Some(params)
+    }
+
+    fn build_match(&self, route_idx: usize, params: HashMap<String, String>, request: &Request) -> RouteMatch {
+        let parsed = &self.routes[route_idx];
+        RouteMatch {
+            route: parsed.definition.clone(),
+            resolved_session_id: parsed.session_template.render(
+                &params,
+                &request.query,
+                request.locale.as_deref(),
+                &self.session_escape,
+            ),
+            params,
+            layouts: self.layout_chain(parsed),
+            normalized_path: request.path.to_path(request.trailing_slash),
+            query: request.query.clone(),
+            fragment: request.fragment.clone(),
+            locale: request.locale.clone(),
+            slot: parsed.slot.clone(),
+            slots: HashMap::new(),
+            intercepted: None,
+        }
+    }
+
+    /// Candidate route indices of `table` for a path, highest precedence first
+    fn candidates(&self, table: &RouteTable, path_segments: &[&str]) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        if let Some(trie) = self.tries.get(table) {
+            trie.collect(path_segments, self.case_sensitive, &mut candidates);
+        }
+        candidates.sort_unstable_by_key(|&idx| self.precedence(idx));
+        candidates
+    }
+
+    /// Rebuild the name index and tries after routes were removed or replaced
+    fn reindex(&mut self) {
+        self.names = self
+            .routes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, route)| route.definition.name().map(|name| (name, idx)))
+            .collect();
+        self.rebuild_trie();
+    }
+
+    /// Recompile the tries from `routes`
+    fn rebuild_trie(&mut self) {
+        self.tries.clear();
+        for (route_idx, route) in self.routes.iter().enumerate() {
+            self.tries
+                .entry(route.table.clone())
+                .or_default()
+                .insert(&route.segments, route_idx, self.case_sensitive);
+        }
+    }
+
+    /// Sort key for a route: more specific host, then more specific path, then
+    /// routes that accept any method, then earlier registration
+    fn precedence(&self, route_idx: usize) -> (std::cmp::Reverse<usize>, std::cmp::Reverse<usize>, bool, usize) {
+        let route = &self.routes[route_idx];
+        (
+            std::cmp::Reverse(route.host_specificity),
+            std::cmp::Reverse(route.specificity),
+            route.methods.is_some(),
+            route_idx,
+        )
+    }
+
+    /// `precedence` for requests with a method, where routes with a method
+    /// list come before routes that accept any method
+    fn method_precedence(&self, route_idx: usize) -> (std::cmp::Reverse<usize>, std::cmp::Reverse<usize>, bool, usize) {
+        let (host, path, restricted, idx) = self.precedence(route_idx);
+        (host, path, !restricted, idx)
+    }
+
+    /// All route indices, highest precedence first
+    fn ordered_routes(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.routes.len()).collect();
+        order.sort_unstable_by_key(|&idx| self.precedence(idx));
+        order
+    }
+
+    /// Decode the payload written by `to_bytes`
+    fn read_snapshot(r: &mut Reader) -> Result<AeonRouter, SnapshotError> {
+        let mut router = AeonRouter::new();
+        router.case_sensitive = r.bool()?;
+        router.trailing_slash = match r.u8()? {
+            0 => TrailingSlash::Ignore,
+            1 => TrailingSlash::Never,
+            2 => TrailingSlash::Always,
+            other => return Err(SnapshotError::Invalid(format!("invalid trailing slash policy {}", other))),
+        };
+        router.locales = r.strs()?;
+        router.default_locale = r.opt_str()?;
+        router.session_escape = SeparatorEscape {
+            separators: r.str()?,
+            replacement: r.opt_str()?,
+        };
+        router.version = u32::try_from(r.varint()?)
+            .map_err(|_| SnapshotError::Invalid("version out of range".to_string()))?;
+
+        for _ in 0..r.usize()? {
+            let directory = r.str()?;
+            router.layouts.insert(directory, r.str()?);
+        }
+        for _ in 0..r.usize()? {
+            router.routes.push(read_route(r)?);
+        }
+        for _ in 0..r.usize()? {
+            router.rules.push(CompiledRule::read(r)?);
+        }
+        r.finish()?;
+        router.reindex();
+        Ok(router)
+    }
+}
+
+impl Default for AeonRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why a route pattern or route operation was rejected
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RouteErrorKind {
+    /// A `[` without its `]` or vice versa
+    UnbalancedBrackets,
+    /// `[]`, `[...]` or `[[...]]`
+    EmptyParamName,
+    /// A parameter name that is not an identifier
+    InvalidParamName,
+    /// The same parameter name used twice in one pattern
+    DuplicateParamName,
+    /// A catch-all followed by more segments
+    CatchAllNotLast,
+    /// A character that cannot appear in a static segment
+    InvalidCharacter,
+    /// Brackets used in a way the router does not understand, e.g. `a[b]`
+    InvalidSegment,
+    /// An empty or malformed `(group)`
+    InvalidRouteGroup,
+    /// An unknown or malformed constraint in `[name:constraint]`
+    InvalidConstraint,
+    /// An empty or malformed `@slot`, or a route in two slots
+    InvalidSlot,
+    /// A `(.)`/`(..)`/`(...)` marker without a segment, climbing above the
+    /// root, or used twice
+    InvalidInterceptor,
+    /// Two routes registered with the same name
+    DuplicateRouteName,
+    /// `url_for` was given a pattern, name or component ID that is not registered
+    UnknownRoute,
+    /// `url_for` is missing a required param
+    MissingParam,
+    /// `url_for` was given a param the route does not declare
+    UnexpectedParam,
+    /// A `url_for` param has the wrong type or fails its constraint
+    InvalidParamValue,
+    /// A rule destination that references an unknown param, or a rewrite to
+    /// something other than a path
+    InvalidDestination,
+    /// Rewrites that revisit a URL or chain more than `MAX_REWRITES` times
+    RewriteLoop,
+    /// `evaluate` was given a request that is not valid JSON
+    InvalidRequest,
+    /// `url_for_locale` was given a locale that is not configured
+    UnknownLocale,
+    /// An empty method list or a method that is not an HTTP token
+    InvalidMethod,
+    /// A session ID template referencing a param the route does not declare
+    UnknownTemplateParam,
+    /// A malformed session ID template, e.g. an unclosed `${` or unknown filter
+    InvalidTemplate,
+    /// `apply_route_diff` was given something other than JSON arrays
+    InvalidRouteDiff,
+    /// A malformed host pattern
+    InvalidHost,
+    /// `from_bytes` was given something other than an intact router snapshot
+    InvalidSnapshot,
+    /// `from_bytes` was given a snapshot written with another format version
+    UnsupportedSnapshot,
+}
+
+/// A rejected route pattern (or `url_for` call), with the offending segment and reason
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouteError {
+    pattern: String,
+    segment: String,
+    kind: RouteErrorKind,
+    reason: String,
+}
 
-use wasm_bindgen::prelude::*;
-use std::collections::HashMap;
-use crate::{RouteDefinition, RouteMatch};
+impl RouteError {
+    pub(crate) fn new(pattern: &str, segment: &str, kind: RouteErrorKind, reason: &str) -> Self {
+        Self {
+            pattern: pattern.to_string(),
+            segment: segment.to_string(),
+            kind,
+            reason: reason.to_string(),
+        }
+    }
+}
 
-/// Segment type for route pattern parsing
-#[derive(Clone, Debug, PartialEq)]
-enum Segment {
-    /// Static segment like "blog" or "about"
-    Static(String),
-    /// Dynamic segment like [slug]
-    Dynamic(String),
-    /// Catch-all segment like [...path]
-    CatchAll(String),
-    /// Optional catch-all like [[...slug]]
-    OptionalCatchAll(String),
+#[wasm_bindgen]
+impl RouteError {
+    /// The full pattern that was rejected
+    #[wasm_bindgen(getter)]
+    pub fn pattern(&self) -> String {
+        self.pattern.clone()
+    }
+
+    /// The segment that caused the rejection
+    #[wasm_bindgen(getter)]
+    pub fn segment(&self) -> String {
+        self.segment.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> RouteErrorKind {
+        self.kind
+    }
+
+    /// Human-readable explanation
+    #[wasm_bindgen(getter)]
+    pub fn reason(&self) -> String {
+        self.reason.clone()
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string_js(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
 }
 
-/// Parsed route pattern
-#[derive(Clone, Debug)]
-struct ParsedRoute {
-    segments: Vec<Segment>,
-    definition: RouteDefinition,
+impl std::fmt::Display for RouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.segment.is_empty() {
+            write!(f, "Route {}: {}", self.pattern, self.reason)
+        } else {
+            write!(f, "Route {} at segment {:?}: {}", self.pattern, self.segment, self.reason)
+        }
+    }
 }
 
-/// The Aeon Router - matches URLs to routes
+impl std::error::Error for RouteError {}
+
+/// How two routes conflict
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictKind {
+    /// Both patterns compile to the same matcher, e.g. `/blog/[slug]` and
+    /// `/blog/[id]`; registration order picks the winner
+    Ambiguous,
+    /// A more specific pattern matches every path of a broader one
+    Shadowed,
+}
+
+/// A pair of routes where `shadowed` can never be matched
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouteConflict {
+    pub kind: ConflictKind,
+    /// Pattern of the route that wins
+    pub winner: String,
+    /// Pattern of the route that never matches
+    pub shadowed: String,
+}
+
+/// Why a route did not match a path (see `AeonRouter::explain`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MismatchReason {
+    /// A static segment differs from the path segment
+    StaticMismatch,
+    /// The path ends before the pattern does
+    MissingSegment,
+    /// The path has segments after the pattern ends
+    LeftoverSegments,
+    /// A constrained segment rejected the path segment
+    ConstraintFailure,
+    /// The route has a host pattern and the request has no host
+    HostRequired,
+}
+
+/// The first failing segment of a route for a path
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SegmentMismatch {
+    pub reason: MismatchReason,
+    /// Index of the path segment (after locale stripping)
+    pub segment_index: usize,
+    /// The pattern segment that failed, in pattern syntax
+    pub expected: Option<String>,
+    /// The path segment at `segment_index`, if any
+    pub actual: Option<String>,
+}
+
+/// How one route fared against a path
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RouteExplanation {
+    pattern: String,
+    matched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mismatch: Option<SegmentMismatch>,
+}
+
+/// Report produced by `AeonRouter::explain`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct MatchExplanation {
+    normalized_path: String,
+    /// Path segments compared against patterns
+    segments: Vec<String>,
+    locale: Option<String>,
+    /// Set when the trailing-slash policy rejects the path before matching
+    rejected: Option<String>,
+    /// Pattern of the route `match_route` returns
+    matched: Option<String>,
+    /// Every main route in precedence order
+    routes: Vec<RouteExplanation>,
+}
+
+/// Outcome of `AeonRouter::match_route_with_method`
 #[wasm_bindgen]
-pub struct AeonRouter {
-    routes: Vec<ParsedRoute>,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MethodMatchStatus {
+    Matched,
+    /// Routes match the path but none allows the method (respond 405)
+    MethodNotAllowed,
+    /// No route matches the path (respond 404)
+    NotFound,
 }
 
+/// Result of `AeonRouter::match_route_with_method`
 #[wasm_bindgen]
-impl AeonRouter {
-    #[wasm_bindgen(constructor)]
-    pub fn new() -> Self {
-        Self { routes: Vec::new() }
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MethodMatch {
+    status: MethodMatchStatus,
+    route_match: Option<RouteMatch>,
+    /// Methods allowed for the path when the status is `MethodNotAllowed`
+    allow: Vec<String>,
+}
+
+impl MethodMatch {
+    fn not_found() -> Self {
+        Self {
+            status: MethodMatchStatus::NotFound,
+            route_match: None,
+            allow: Vec::new(),
+        }
     }
+}
 
-    /// Add a route to the router
-    pub fn add_route(&mut self, definition: RouteDefinition) {
-        let segments = parse_pattern(&definition.pattern());
-        self.routes.push(ParsedRoute {
-            segments,
-            definition,
-        });
-        // Sort routes by specificity (static > dynamic > catch-all)
-        self.routes.sort_by(|a, b| {
-            route_specificity(&b.segments).cmp(&route_specificity(&a.segments))
-        });
+#[wasm_bindgen]
+impl MethodMatch {
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> MethodMatchStatus {
+        self.status
     }
 
-    /// Match a URL path to a route
-    pub fn match_route(&self, path: &str) -> Option<RouteMatch> {
-        let path_segments: Vec<&str> = path
-            .trim_start_matches('/')
-            .trim_end_matches('/')
-            .split('/')
-            .filter(|s| !s.is_empty())
-            .collect();
+    #[wasm_bindgen(getter)]
+    pub fn route_match(&self) -> Option<RouteMatch> {
+        self.route_match.clone()
+    }
 
-        for parsed in &self.routes {
-            if let Some(params) = match_segments(&parsed.segments, &path_segments) {
-                let resolved_session_id = resolve_session_id(
-                    &parsed.definition.session_id(),
-                    &params,
-                );
-                return Some(RouteMatch {
-                    route: parsed.definition.clone(),
-                    params,
-                    resolved_session_id,
-                });
+    /// Allowed methods as a JSON array
+    #[wasm_bindgen(getter)]
+    pub fn allow_json(&self) -> String {
+        serde_json::to_string(&self.allow).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    /// Value for the `Allow` response header, e.g. `GET, HEAD, POST`
+    #[wasm_bindgen(getter)]
+    pub fn allow_header(&self) -> String {
+        self.allow.join(", ")
+    }
+}
+
+/// Outcome of `AeonRouter::load_manifest`
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ManifestReport {
+    /// Number of routes added to the router
+    loaded: usize,
+    /// Entries that were rejected
+    errors: Vec<ManifestEntryError>,
+}
+
+/// A manifest entry that could not be loaded
+#[wasm_bindgen]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ManifestEntryError {
+    /// Position in the manifest's route list (None if the document itself is invalid)
+    index: Option<usize>,
+    /// The entry's pattern, if it had one
+    pattern: Option<String>,
+    message: String,
+    /// Set when the pattern itself was rejected
+    kind: Option<RouteErrorKind>,
+    /// The offending pattern segment, if any
+    segment: Option<String>,
+}
+
+#[wasm_bindgen]
+impl ManifestReport {
+    #[wasm_bindgen(getter)]
+    pub fn loaded(&self) -> usize {
+        self.loaded
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// True if every entry was loaded
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Rejected entries
+    #[wasm_bindgen(getter)]
+    pub fn errors(&self) -> Vec<ManifestEntryError> {
+        self.errors.clone()
+    }
+
+    /// Rejected entries as JSON: `[{ index, pattern, message, kind, segment }]`
+    pub fn errors_json(&self) -> String {
+        serde_json::to_string(&self.errors).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+#[wasm_bindgen]
+impl ManifestEntryError {
+    #[wasm_bindgen(getter)]
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pattern(&self) -> Option<String> {
+        self.pattern.clone()
+    }
+
+    /// Human-readable reason
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.message.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> Option<RouteErrorKind> {
+        self.kind
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn segment(&self) -> Option<String> {
+        self.segment.clone()
+    }
+}
+
+/// Extract the raw route entries from a manifest document
+fn parse_manifest_entries(json: &str) -> Result<(Vec<serde_json::Value>, HashMap<String, String>), String> {
+    let document: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse route manifest: {}", e))?;
+    match document {
+        serde_json::Value::Array(entries) => Ok((entries, HashMap::new())),
+        serde_json::Value::Object(mut map) => {
+            let layouts = match map.remove("layouts") {
+                None => HashMap::new(),
+                Some(layouts) => serde_json::from_value(layouts)
+                    .map_err(|_| "Route manifest \"layouts\" must map directories to layout IDs".to_string())?,
+            };
+            match map.remove("routes") {
+                Some(serde_json::Value::Array(entries)) => Ok((entries, layouts)),
+                Some(_) => Err("Route manifest \"routes\" must be an array".to_string()),
+                None => Err("Route manifest has no \"routes\" array".to_string()),
             }
         }
-        None
+        _ => Err("Route manifest must be an object or an array".to_string()),
     }
+}
 
-    /// Get all registered routes (for debugging)
-    pub fn get_routes_json(&self) -> String {
-        let patterns: Vec<String> = self.routes
-            .iter()
-            .map(|r| r.definition.pattern())
-            .collect();
-        serde_json::to_string(&patterns).unwrap_or_else(|_| "[]".to_string())
+/// Manifest-level checks on a single route definition
+fn validate_manifest_entry(definition: &RouteDefinition, seen: &HashSet<String>) -> Result<(), String> {
+    if definition.component_id().is_empty() {
+        return Err("Route has an empty component_id".to_string());
     }
+    if seen.contains(&route_key(definition)) {
+        return Err(format!("Duplicate route pattern {}", route_key(definition)));
+    }
+    Ok(())
+}
 
-    /// Check if a route exists for the given path
-    pub fn has_route(&self, path: &str) -> bool {
-        self.match_route(path).is_some()
+/// Host, pattern and methods of a route, e.g. `[tenant].example.com/api GET,POST`
+fn route_key(definition: &RouteDefinition) -> String {
+    let mut key = format!("{}{}", definition.host().unwrap_or_default(), definition.pattern());
+    if let Some(mut methods) = definition.methods() {
+        methods.iter_mut().for_each(|m| m.make_ascii_uppercase());
+        methods.sort();
+        key.push(' ');
+        key.push_str(&methods.join(","));
     }
+    key
 }
 
-impl Default for AeonRouter {
-    fn default() -> Self {
-        Self::new()
+/// A dynamic param value for `url_for`
+fn scalar_param(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
     }
 }
 
-/// Parse a route pattern into segments
-fn parse_pattern(pattern: &str) -> Vec<Segment> {
-    pattern
-        .trim_start_matches('/')
-        .trim_end_matches('/')
-        .split('/')
-        .filter(|s| !s.is_empty())
-        .filter(|s| !is_route_group(s)) // Skip route groups like (dashboard)
-        .map(|s| {
-            if s.starts_with("[[...") && s.ends_with("]]") {
-                // Optional catch-all: [[...slug]]
-                let name = s[5..s.len() - 2].to_string();
-                Segment::OptionalCatchAll(name)
-            } else if s.starts_with("[...") && s.ends_with(']') {
-                // Catch-all: [...path]
-                let name = s[4..s.len() - 1].to_string();
-                Segment::CatchAll(name)
-            } else if s.starts_with('[') && s.ends_with(']') {
-                // Dynamic: [slug]
-                let name = s[1..s.len() - 1].to_string();
-                Segment::Dynamic(name)
-            } else {
-                // Static
-                Segment::Static(s.to_string())
+/// Catch-all param values for `url_for`: an array, or a `/`-separated string
+fn catch_all_param(value: &serde_json::Value) -> Option<Vec<String>> {
+    match value {
+        serde_json::Value::Array(items) => items.iter().map(scalar_param).collect(),
+        serde_json::Value::String(s) => Some(s.split('/').filter(|p| !p.is_empty()).map(str::to_string).collect()),
+        _ => None,
+    }
+}
+
+/// Canonical layout key for a directory: `/` plus its pattern segments
+fn directory_key(directory: &str) -> String {
+    format!("/{}", split_pattern(directory).join("/"))
+}
+
+/// Layout keys of the directories containing a page, root first
+fn pattern_directories(pattern: &str) -> Vec<String> {
+    let mut directories = vec!["/".to_string()];
+    let mut current = String::new();
+    for segment in split_pattern(pattern) {
+        current.push('/');
+        current.push_str(segment);
+        directories.push(current.clone());
+    }
+    directories
+}
+
+/// Trie key for a static segment
+fn static_key(value: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        value.to_string()
+    } else {
+        value.to_lowercase()
+    }
+}
+
+/// Parse and validate a route pattern into segments, slot and interception point
+pub(crate) fn parse_pattern(pattern: &str) -> Result<ParsedPattern, RouteError> {
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut param_names = HashSet::new();
+    let mut catch_all: Option<&str> = None;
+    let mut slot: Option<String> = None;
+    let mut intercept_from: Option<Vec<Segment>> = None;
+
+    for raw in split_pattern(pattern) {
+        if let Some(catch_all) = catch_all {
+            return Err(RouteError::new(
+                pattern,
+                catch_all,
+                RouteErrorKind::CatchAllNotLast,
+                "a catch-all segment must be the last segment of the pattern",
+            ));
+        }
+        let error = |kind: RouteErrorKind, reason: &str| RouteError::new(pattern, raw, kind, reason);
+
+        if let Some(name) = raw.strip_prefix('@') {
+            // Parallel route slot: @modal
+            if slot.is_some() {
+                return Err(error(RouteErrorKind::InvalidSlot, "a route can belong to only one slot"));
             }
-        })
-        .collect()
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(error(
+                    RouteErrorKind::InvalidSlot,
+                    "slot names must be non-empty and contain only letters, digits, '-' or '_'",
+                ));
+            }
+            slot = Some(name.to_string());
+            continue;
+        }
+
+        let raw_segment = match parse_interceptor(raw) {
+            None => raw,
+            Some((level, rest)) => {
+                if intercept_from.is_some() {
+                    return Err(error(
+                        RouteErrorKind::InvalidInterceptor,
+                        "a route can be intercepted only once",
+                    ));
+                }
+                if rest.is_empty() {
+                    return Err(error(
+                        RouteErrorKind::InvalidInterceptor,
+                        "an interception marker must prefix a segment, e.g. (.)photo",
+                    ));
+                }
+                let keep = match level {
+                    InterceptLevel::Same => Some(segments.len()),
+                    InterceptLevel::Up(levels) => segments.len().checked_sub(levels),
+                    InterceptLevel::Root => Some(0),
+                };
+                let Some(keep) = keep else {
+                    return Err(error(
+                        RouteErrorKind::InvalidInterceptor,
+                        "the interceptor climbs above the root",
+                    ));
+                };
+                intercept_from = Some(segments.clone());
+                segments.truncate(keep);
+                param_names = segments.iter().filter_map(Segment::param_name).map(str::to_string).collect();
+                rest
+            }
+        };
+
+        let Some(segment) = parse_segment(pattern, raw_segment)? else {
+            continue; // Route groups like (dashboard) are not part of the URL
+        };
+
+        if let Some(name) = segment.param_name() {
+            if !param_names.insert(name.to_string()) {
+                return Err(error(
+                    RouteErrorKind::DuplicateParamName,
+                    &format!("parameter \"{}\" is declared more than once", name),
+                ));
+            }
+        }
+        if let Segment::CatchAll(_) | Segment::OptionalCatchAll(_) = &segment {
+            catch_all = Some(raw);
+        }
+        segments.push(segment);
+    }
+    Ok(ParsedPattern {
+        segments,
+        slot,
+        intercept_from,
+    })
+}
+
+/// Split an interception marker (`(.)`, `(..)`, `(..)(..)`, `(...)`) off a segment
+fn parse_interceptor(raw: &str) -> Option<(InterceptLevel, &str)> {
+    if let Some(rest) = raw.strip_prefix("(...)") {
+        return Some((InterceptLevel::Root, rest));
+    }
+    if let Some(rest) = raw.strip_prefix("(.)") {
+        return Some((InterceptLevel::Same, rest));
+    }
+    let mut rest = raw;
+    let mut levels = 0;
+    while let Some(next) = rest.strip_prefix("(..)") {
+        rest = next;
+        levels += 1;
+    }
+    (levels > 0).then_some((InterceptLevel::Up(levels), rest))
+}
+
+/// Parse a single pattern segment; route groups yield `None`
+fn parse_segment(pattern: &str, raw: &str) -> Result<Option<Segment>, RouteError> {
+    let error = |kind: RouteErrorKind, reason: &str| RouteError::new(pattern, raw, kind, reason);
+
+    if is_route_group(raw) {
+        let name = &raw[1..raw.len() - 1];
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || "-_.".contains(c)) {
+            return Err(error(
+                RouteErrorKind::InvalidRouteGroup,
+                "route group names must be non-empty and contain only letters, digits, '-', '_' or '.'",
+            ));
+        }
+        return Ok(None);
+    }
+
+    if !brackets_balance(raw) {
+        return Err(error(RouteErrorKind::UnbalancedBrackets, "'[' and ']' do not balance"));
+    }
+
+    if !raw.contains('[') {
+        if let Some(c) = raw.chars().find(|&c| !is_valid_static_char(c)) {
+            return Err(error(
+                RouteErrorKind::InvalidCharacter,
+                &format!("character {:?} is not allowed in a static segment", c),
+            ));
+        }
+        return Ok(Some(Segment::Static(raw.to_string())));
+    }
+
+    let param_name = |name: &str| -> Result<String, RouteError> {
+        if name.is_empty() {
+            return Err(error(RouteErrorKind::EmptyParamName, "parameter name is empty"));
+        }
+        if name.contains('[') || name.contains(']') {
+            return Err(error(
+                RouteErrorKind::InvalidSegment,
+                "a segment can declare only one parameter",
+            ));
+        }
+        if !is_valid_param_name(name) {
+            return Err(error(
+                RouteErrorKind::InvalidParamName,
+                &format!("parameter name \"{}\" must be an identifier ([A-Za-z_][A-Za-z0-9_]*)", name),
+            ));
+        }
+        Ok(name.to_string())
+    };
+
+    if let Some(name) = raw.strip_prefix("[[...").and_then(|r| r.strip_suffix("]]")) {
+        // Optional catch-all: [[...slug]]
+        Ok(Some(Segment::OptionalCatchAll(param_name(name)?)))
+    } else if raw.starts_with("[[") {
+        Err(error(
+            RouteErrorKind::InvalidSegment,
+            "only catch-alls can be optional: use [[...name]]",
+        ))
+    } else if let Some(name) = raw.strip_prefix("[...").and_then(|r| r.strip_suffix(']')) {
+        // Catch-all: [...path]
+        Ok(Some(Segment::CatchAll(param_name(name)?)))
+    } else if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        // Dynamic: [slug] or constrained [slug:spec]
+        let Some((name, spec)) = inner.split_once(':') else {
+            return Ok(Some(Segment::Dynamic(param_name(inner)?, None)));
+        };
+        let name = param_name(name)?;
+        let constraint = ParamConstraint::parse(spec)
+            .map_err(|reason| error(RouteErrorKind::InvalidConstraint, &reason))?;
+        Ok(Some(Segment::Dynamic(name, Some(constraint))))
+    } else {
+        Err(error(
+            RouteErrorKind::InvalidSegment,
+            "a dynamic segment must span the whole segment, e.g. [slug]",
+        ))
+    }
+}
+
+/// Split a pattern on `/`, except inside brackets so that regex constraints
+/// like `[slug:/^[a-z]+$/]` stay in one segment
+fn split_pattern(pattern: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut depth = 0usize;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in pattern.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if depth > 0 => escaped = true,
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            '/' if depth == 0 => {
+                segments.push(&pattern[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&pattern[start..]);
+    segments.retain(|s| !s.is_empty());
+    segments
+}
+
+/// Whether every `[` has a matching `]` (escaped brackets inside a constraint don't count)
+fn brackets_balance(segment: &str) -> bool {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for c in segment.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if depth > 0 => escaped = true,
+            '[' => depth += 1,
+            ']' if depth == 0 => return false,
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+/// Parameter names must be usable as `$name` in session ID templates
+fn is_valid_param_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Characters allowed verbatim in a static path segment (RFC 3986 pchar, plus non-ASCII)
+fn is_valid_static_char(c: char) -> bool {
+    c.is_alphanumeric() || "-._~!$&'()*+,;=:@".contains(c)
 }
 
 /// Check if a segment is a route group (parentheses)
@@ -144,7 +2154,8 @@ fn route_specificity(segments: &[Segment]) -> usize {
         let position_weight = 1000 - i; // Earlier segments are more important
         score += match segment {
             Segment::Static(_) => position_weight * 10,
-            Segment::Dynamic(_) => position_weight * 5,
+            Segment::Dynamic(_, Some(_)) => position_weight * 7,
+            Segment::Dynamic(_, None) => position_weight * 5,
             Segment::CatchAll(_) => 1,
             Segment::OptionalCatchAll(_) => 0,
         };
@@ -152,35 +2163,98 @@ fn route_specificity(segments: &[Segment]) -> usize {
     score
 }
 
+/// Whether every path matched by `b` is also matched by `a`
+fn covers(a: &[Segment], b: &[Segment]) -> bool {
+    match (a.first(), b.first()) {
+        (None, None) => true,
+        (None, Some(_)) => false,
+        (Some(Segment::OptionalCatchAll(_)), _) => true,
+        (Some(Segment::CatchAll(_)), Some(next)) => !matches!(next, Segment::OptionalCatchAll(_)),
+        (Some(Segment::Static(x)), Some(Segment::Static(y))) => x == y && covers(&a[1..], &b[1..]),
+        (Some(Segment::Dynamic(_, constraint)), Some(Segment::Static(value))) => {
+            constraint.as_ref().is_none_or(|c| c.matches(value)) && covers(&a[1..], &b[1..])
+        }
+        (Some(Segment::Dynamic(_, constraint)), Some(Segment::Dynamic(_, other))) => {
+            let accepts = match (constraint, other) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(c), Some(o)) => c.accepts_all_of(o),
+            };
+            accepts && covers(&a[1..], &b[1..])
+        }
+        _ => false,
+    }
+}
+
+/// Whether two patterns differ only in parameter names
+fn same_matcher(a: &[Segment], b: &[Segment]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|pair| match pair {
+            (Segment::Static(x), Segment::Static(y)) => x == y,
+            (Segment::Dynamic(_, x), Segment::Dynamic(_, y)) => x == y,
+            (Segment::CatchAll(_), Segment::CatchAll(_))
+            | (Segment::OptionalCatchAll(_), Segment::OptionalCatchAll(_)) => true,
+            _ => false,
+        })
+}
+
 /// Match path segments against route segments, returning extracted params
-fn match_segments(
+pub(crate) fn match_segments(
     route_segments: &[Segment],
     path_segments: &[&str],
+    case_sensitive: bool,
 ) -> Option<HashMap<String, String>> {
+    explain_segments(route_segments, path_segments, case_sensitive).ok()
+}
+
+/// Like `match_segments`, but reports the first segment that failed
+fn explain_segments(
+    route_segments: &[Segment],
+    path_segments: &[&str],
+    case_sensitive: bool,
+) -> Result<HashMap<String, String>, SegmentMismatch> {
     let mut params = HashMap::new();
     let mut path_idx = 0;
+    let mismatch = |reason: MismatchReason, path_idx: usize, expected: Option<&Segment>| SegmentMismatch {
+        reason,
+        segment_index: path_idx,
+        expected: expected.map(Segment::to_string),
+        actual: path_segments.get(path_idx).map(|s| s.to_string()),
+    };
 
-    for (_route_idx, segment) in route_segments.iter().enumerate() {
+    for segment in route_segments {
         match segment {
             Segment::Static(expected) => {
                 if path_idx >= path_segments.len() {
-                    return None;
+                    return Err(mismatch(MismatchReason::MissingSegment, path_idx, Some(segment)));
                 }
-                if path_segments[path_idx] != expected {
-                    return None;
+                let actual = path_segments[path_idx];
+                let equal = if case_sensitive {
+                    actual == expected
+                } else {
+                    actual.to_lowercase() == expected.to_lowercase()
+                };
+                if !equal {
+                    return Err(mismatch(MismatchReason::StaticMismatch, path_idx, Some(segment)));
                 }
                 path_idx += 1;
             }
-            Segment::Dynamic(name) => {
+            Segment::Dynamic(name, constraint) => {
                 if path_idx >= path_segments.len() {
-                    return None;
+                    return Err(mismatch(MismatchReason::MissingSegment, path_idx, Some(segment)));
+                }
+                if let Some(constraint) = constraint {
+                    if !constraint.matches(path_segments[path_idx]) {
+                        return Err(mismatch(MismatchReason::ConstraintFailure, path_idx, Some(segment)));
+                    }
                 }
                 params.insert(name.clone(), path_segments[path_idx].to_string());
                 path_idx += 1;
             }
             Segment::CatchAll(name) => {
                 if path_idx >= path_segments.len() {
-                    return None; // Catch-all must match at least one segment
+                    // Catch-all must match at least one segment
+                    return Err(mismatch(MismatchReason::MissingSegment, path_idx, Some(segment)));
                 }
                 let remaining: Vec<&str> = path_segments[path_idx..].to_vec();
                 params.insert(name.clone(), remaining.join("/"));
@@ -198,26 +2272,304 @@ fn match_segments(
         }
     }
 
-    // All path segments must be consumed (unless we had a catch-all)
-    if path_idx == path_segments.len() {
-        Some(params)
-    } else {
-        None
+    // All path segments must be consumed (unless we had a catch-all)
+    if path_idx == path_segments.len() {
+        Ok(params)
+    } else {
+        Err(mismatch(MismatchReason::LeftoverSegments, path_idx, None))
+    }
+}
+
+/// Parse a host pattern like `app.example.com`, `[tenant].example.com` or
+/// `*.example.com`. Host params must not reuse path param names.
+fn parse_host(pattern: &str, host: &str, segments: &[Segment]) -> Result<Vec<HostLabel>, RouteError> {
+    let mut names = HashSet::new();
+    host.trim_end_matches('.')
+        .split('.')
+        .map(|label| {
+            let error = |kind: RouteErrorKind, reason: &str| RouteError::new(pattern, label, kind, reason);
+            if label == "*" {
+                return Ok(HostLabel::Wildcard);
+            }
+            if let Some(name) = label.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                if !is_valid_param_name(name) {
+                    return Err(error(
+                        RouteErrorKind::InvalidHost,
+                        "host params must be identifiers, e.g. [tenant]",
+                    ));
+                }
+                if !names.insert(name) || segments.iter().any(|s| s.param_name() == Some(name)) {
+                    return Err(error(
+                        RouteErrorKind::DuplicateParamName,
+                        &format!("parameter \"{}\" is declared more than once", name),
+                    ));
+                }
+                return Ok(HostLabel::Param(name.to_string()));
+            }
+            if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(error(
+                    RouteErrorKind::InvalidHost,
+                    "host labels must be letters, digits and '-', a [param] or *",
+                ));
+            }
+            Ok(HostLabel::Static(label.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// Host specificity for ordering (higher = more specific); any host pattern
+/// ranks above no host
+fn host_specificity(labels: &[HostLabel]) -> usize {
+    labels
+        .iter()
+        .map(|label| match label {
+            HostLabel::Static(_) => 3,
+            HostLabel::Param(_) => 2,
+            HostLabel::Wildcard => 1,
+        })
+        .sum()
+}
+
+fn snapshot_error(error: SnapshotError) -> RouteError {
+    match error {
+        SnapshotError::Invalid(reason) => RouteError::new("", "", RouteErrorKind::InvalidSnapshot, &reason),
+        SnapshotError::UnsupportedVersion(version) => RouteError::new(
+            "",
+            "",
+            RouteErrorKind::UnsupportedSnapshot,
+            &format!(
+                "snapshot format version {} is not supported (expected {})",
+                version,
+                snapshot::FORMAT_VERSION
+            ),
+        ),
+    }
+}
+
+/// Encode a compiled route for `to_bytes`. The session template is not
+/// stored; it is recompiled from `session_id` on load.
+fn write_route(w: &mut Writer, route: &ParsedRoute) {
+    let definition = &route.definition;
+    w.str(&definition.pattern);
+    w.str(&definition.session_id);
+    w.str(&definition.component_id);
+    w.opt_str(definition.layout.as_deref());
+    w.bool(definition.is_aeon);
+    w.opt_str(definition.name.as_deref());
+    w.opt_str(definition.host.as_deref());
+    w.opt_strs(definition.methods.as_deref());
+    let metadata = definition
+        .metadata
+        .as_ref()
+        .map(|metadata| serde_json::Value::Object(metadata.clone()).to_string());
+    w.opt_str(metadata.as_deref());
+
+    // The table, specificities, methods and directories are derived again
+    // on load, so a snapshot cannot carry values that disagree with these
+    write_segments(w, &route.segments);
+    w.opt_str(route.slot.as_deref());
+    w.bool(route.intercept_from.is_some());
+    if let Some(from) = &route.intercept_from {
+        write_segments(w, from);
+    }
+    w.bool(route.host.is_some());
+    if let Some(host) = &route.host {
+        w.usize(host.len());
+        for label in host {
+            match label {
+                HostLabel::Static(value) => {
+                    w.u8(0);
+                    w.str(value);
+                }
+                HostLabel::Param(name) => {
+                    w.u8(1);
+                    w.str(name);
+                }
+                HostLabel::Wildcard => w.u8(2),
+            }
+        }
+    }
+}
+
+fn read_route(r: &mut Reader) -> Result<ParsedRoute, SnapshotError> {
+    let mut definition = RouteDefinition::new(r.str()?, r.str()?, r.str()?, r.opt_str()?, r.bool()?);
+    definition.name = r.opt_str()?;
+    definition.host = r.opt_str()?;
+    definition.methods = r.opt_strs()?;
+    definition.metadata = match r.opt_str()? {
+        None => None,
+        Some(json) => Some(
+            serde_json::from_str(&json).map_err(|e| SnapshotError::Invalid(format!("invalid metadata: {}", e)))?,
+        ),
+    };
+
+    let segments = read_segments(r)?;
+    let slot = r.opt_str()?;
+    let intercept_from = if r.bool()? { Some(read_segments(r)?) } else { None };
+    let host = if r.bool()? {
+        let labels = (0..r.usize()?)
+            .map(|_| {
+                Ok(match r.u8()? {
+                    0 => HostLabel::Static(r.str()?),
+                    1 => HostLabel::Param(r.str()?),
+                    2 => HostLabel::Wildcard,
+                    other => return Err(SnapshotError::Invalid(format!("invalid host label {}", other))),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Some(labels)
+    } else {
+        None
+    };
+    let methods = definition
+        .methods()
+        .map(|methods| parse_methods(&definition.pattern(), &methods))
+        .transpose()
+        .map_err(|error| SnapshotError::Invalid(error.to_string()))?;
+    let session_template = compile_session_template(&definition, &segments, host.as_deref())
+        .map_err(|error| SnapshotError::Invalid(error.to_string()))?;
+    Ok(ParsedRoute::new(definition, segments, slot, intercept_from, host, methods, session_template))
+}
+
+/// Encode pattern segments; constraints are stored in pattern syntax
+pub(crate) fn write_segments(w: &mut Writer, segments: &[Segment]) {
+    w.usize(segments.len());
+    for segment in segments {
+        match segment {
+            Segment::Static(value) => {
+                w.u8(0);
+                w.str(value);
+            }
+            Segment::Dynamic(name, constraint) => {
+                w.u8(1);
+                w.str(name);
+                w.opt_str(constraint.as_ref().map(ToString::to_string).as_deref());
+            }
+            Segment::CatchAll(name) => {
+                w.u8(2);
+                w.str(name);
+            }
+            Segment::OptionalCatchAll(name) => {
+                w.u8(3);
+                w.str(name);
+            }
+        }
+    }
+}
+
+/// Decode segments written by `write_segments`, recompiling regex constraints
+pub(crate) fn read_segments(r: &mut Reader) -> Result<Vec<Segment>, SnapshotError> {
+    (0..r.usize()?)
+        .map(|_| {
+            Ok(match r.u8()? {
+                0 => Segment::Static(r.str()?),
+                1 => {
+                    let name = r.str()?;
+                    let constraint = r
+                        .opt_str()?
+                        .map(|spec| ParamConstraint::parse(&spec))
+                        .transpose()
+                        .map_err(SnapshotError::Invalid)?;
+                    Segment::Dynamic(name, constraint)
+                }
+                2 => Segment::CatchAll(r.str()?),
+                3 => Segment::OptionalCatchAll(r.str()?),
+                other => return Err(SnapshotError::Invalid(format!("invalid segment tag {}", other))),
+            })
+        })
+        .collect()
+}
+
+fn unknown_route(route: &str) -> RouteError {
+    RouteError::new(route, "", RouteErrorKind::UnknownRoute, "no route with this pattern, name or component ID")
+}
+
+/// Compile a route's session ID template against its path and host params
+fn compile_session_template(
+    definition: &RouteDefinition,
+    segments: &[Segment],
+    host: Option<&[HostLabel]>,
+) -> Result<SessionTemplate, RouteError> {
+    let host_params = host.unwrap_or_default().iter().filter_map(|label| match label {
+        HostLabel::Param(name) => Some(name.as_str()),
+        _ => None,
+    });
+    let params: Vec<&str> = segments.iter().filter_map(Segment::param_name).chain(host_params).collect();
+    SessionTemplate::parse(&definition.session_id(), &params).map_err(|error| {
+        let kind = if error.unknown_param {
+            RouteErrorKind::UnknownTemplateParam
+        } else {
+            RouteErrorKind::InvalidTemplate
+        };
+        RouteError::new(&definition.pattern(), &error.reference, kind, &format!("session ID template: {}", error.reason))
+    })
+}
+
+/// Uppercase and validate a route's method list
+fn parse_methods(pattern: &str, methods: &[String]) -> Result<Vec<String>, RouteError> {
+    if methods.is_empty() {
+        return Err(RouteError::new(
+            pattern,
+            "",
+            RouteErrorKind::InvalidMethod,
+            "the method list is empty; omit it to allow every method",
+        ));
     }
+    methods
+        .iter()
+        .map(|method| {
+            if method.is_empty() || !method.bytes().all(|b| b.is_ascii_alphabetic()) {
+                return Err(RouteError::new(
+                    pattern,
+                    "",
+                    RouteErrorKind::InvalidMethod,
+                    &format!("{:?} is not an HTTP method", method),
+                ));
+            }
+            Ok(method.to_ascii_uppercase())
+        })
+        .collect()
 }
 
-/// Resolve session ID template with actual params
-fn resolve_session_id(template: &str, params: &HashMap<String, String>) -> String {
-    let mut result = template.to_string();
-    for (key, value) in params {
-        result = result.replace(&format!("${}", key), value);
+/// Lowercase a request host and strip its port and trailing dot
+fn normalize_host(host: &str) -> String {
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Host params if a normalized host matches the labels
+fn match_host(labels: &[HostLabel], host: &str) -> Option<HashMap<String, String>> {
+    let parts: Vec<&str> = host.split('.').collect();
+    if parts.len() != labels.len() {
+        return None;
+    }
+    let mut params = HashMap::new();
+    for (label, part) in labels.iter().zip(parts) {
+        match label {
+            _ if part.is_empty() => return None,
+            HostLabel::Static(value) if value != part => return None,
+            HostLabel::Static(_) | HostLabel::Wildcard => {}
+            HostLabel::Param(name) => {
+                params.insert(name.clone(), part.to_string());
+            }
+        }
     }
-    result
+    Some(params)
+}
+
+/// Whether the leading path segments match `prefix` (a pattern without catch-alls)
+fn matches_prefix(prefix: &[Segment], path_segments: &[&str], case_sensitive: bool) -> bool {
+    path_segments.len() >= prefix.len()
+        && match_segments(prefix, &path_segments[..prefix.len()], case_sensitive).is_some()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rules::OutcomeKind;
 
     #[test]
     fn test_static_route() {
@@ -228,7 +2580,7 @@ mod tests {
             "AboutPage".to_string(),
             None,
             false,
-        ));
+        )).unwrap();
 
         let result = router.match_route("/about");
         assert!(result.is_some());
@@ -244,7 +2596,7 @@ mod tests {
             "BlogPost".to_string(),
             None,
             true,
-        ));
+        )).unwrap();
 
         let result = router.match_route("/blog/hello-world");
         assert!(result.is_some());
@@ -262,7 +2614,7 @@ mod tests {
             "ApiHandler".to_string(),
             None,
             false,
-        ));
+        )).unwrap();
 
         let result = router.match_route("/api/users/123/posts");
         assert!(result.is_some());
@@ -281,18 +2633,1032 @@ mod tests {
             "BlogPost".to_string(),
             None,
             true,
-        ));
+        )).unwrap();
         router.add_route(RouteDefinition::new(
             "/blog/featured".to_string(),
             "blog-featured".to_string(),
             "FeaturedPost".to_string(),
             None,
             true,
-        ));
+        )).unwrap();
 
         // Static route should match before dynamic
         let result = router.match_route("/blog/featured");
         assert!(result.is_some());
         assert_eq!(result.unwrap().resolved_session_id(), "blog-featured");
     }
+
+    #[test]
+    fn test_load_manifest() {
+        let manifest = r#"{
+            "version": "1.0.0",
+            "routes": [
+                {"pattern": "/", "sessionId": "index", "componentId": "Home", "isAeon": true},
+                {"pattern": "/blog/[slug]", "session_id": "blog-$slug", "component_id": "BlogPost", "layout": "BlogLayout", "is_aeon": true},
+                {"pattern": "/broken", "componentId": "Broken"},
+                {"pattern": "/blog/[slug]", "sessionId": "dup", "componentId": "Dup"},
+                {"pattern": "/docs/[[...slug]]", "sessionId": "docs", "componentId": "Docs"}
+            ]
+        }"#;
+
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(manifest);
+        assert_eq!(report.loaded(), 3);
+        assert_eq!(report.error_count(), 2);
+
+        let errors: Vec<serde_json::Value> = serde_json::from_str(&report.errors_json()).unwrap();
+        assert_eq!(errors[0]["index"], 2);
+        assert_eq!(errors[0]["pattern"], "/broken");
+        assert_eq!(errors[1]["index"], 3);
+
+        let m = router.match_route("/blog/hello").unwrap();
+        assert_eq!(m.resolved_session_id(), "blog-hello");
+        assert_eq!(m.route().layout(), Some("BlogLayout".to_string()));
+        assert!(router.match_route("/").unwrap().route().is_aeon());
+        assert!(router.has_route("/docs/a/b"));
+    }
+
+    #[test]
+    fn test_load_manifest_rejects_malformed_document() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(r#"{"version": "1.0.0"}"#);
+        assert!(!report.is_ok());
+        assert_eq!(report.loaded(), 0);
+
+        let report = router.load_manifest(r#"[{"pattern": "/a", "sessionId": "a", "componentId": "A"}]"#);
+        assert!(report.is_ok());
+        assert!(router.has_route("/a"));
+    }
+
+    #[test]
+    fn test_pattern_validation() {
+        let cases = [
+            ("/a/[...rest]/b", "[...rest]", RouteErrorKind::CatchAllNotLast),
+            ("/[[...x]]/more", "[[...x]]", RouteErrorKind::CatchAllNotLast),
+            ("/[]", "[]", RouteErrorKind::EmptyParamName),
+            ("/[...]", "[...]", RouteErrorKind::EmptyParamName),
+            ("/[a-b", "[a-b", RouteErrorKind::UnbalancedBrackets),
+            ("/a]", "a]", RouteErrorKind::UnbalancedBrackets),
+            ("/[id]/x/[id]", "[id]", RouteErrorKind::DuplicateParamName),
+            ("/[a-b]", "[a-b]", RouteErrorKind::InvalidParamName),
+            ("/[[slug]]", "[[slug]]", RouteErrorKind::InvalidSegment),
+            ("/post-[id]", "post-[id]", RouteErrorKind::InvalidSegment),
+            ("/[a][b]", "[a][b]", RouteErrorKind::InvalidSegment),
+            ("/what?", "what?", RouteErrorKind::InvalidCharacter),
+            ("/a b", "a b", RouteErrorKind::InvalidCharacter),
+            ("/()/a", "()", RouteErrorKind::InvalidRouteGroup),
+        ];
+        for (pattern, segment, kind) in cases {
+            let error = parse_pattern(pattern).unwrap_err();
+            assert_eq!((error.segment(), error.kind()), (segment.to_string(), kind), "{}", pattern);
+            assert_eq!(error.pattern(), pattern);
+        }
+
+        for pattern in ["/", "/(marketing)/about", "/blog/[slug]", "/docs/[[...slug]]", "/caf\u{e9}/v1.2~x"] {
+            assert!(parse_pattern(pattern).is_ok(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_add_route_rejects_invalid_pattern() {
+        let mut router = AeonRouter::new();
+        let error = router
+            .add_route(RouteDefinition::new(
+                "/a/[...rest]/b".to_string(),
+                "a".to_string(),
+                "A".to_string(),
+                None,
+                false,
+            ))
+            .unwrap_err();
+        assert_eq!(error.kind(), RouteErrorKind::CatchAllNotLast);
+        assert_eq!(router.get_routes_json(), "[]");
+
+        let report = router.load_manifest(r#"[{"pattern": "/[id]/[id]", "sessionId": "x", "componentId": "X"}]"#);
+        let errors: Vec<serde_json::Value> = serde_json::from_str(&report.errors_json()).unwrap();
+        assert_eq!(errors[0]["kind"], "DuplicateParamName");
+        assert_eq!(errors[0]["segment"], "[id]");
+
+        let report = AeonRouter::from_manifest_json(r#"[{"pattern": "/[id]/[id]", "sessionId": "x", "componentId": "X"}]"#)
+            .err()
+            .unwrap();
+        let error = &report.errors()[0];
+        assert_eq!((error.kind(), error.segment()), (Some(RouteErrorKind::DuplicateParamName), Some("[id]".to_string())));
+        assert_eq!(error.index(), Some(0));
+    }
+
+    #[test]
+    fn test_route_conflicts() {
+        let mut router = AeonRouter::new();
+        for pattern in [
+            "/blog/[slug]",
+            "/blog/[id]",
+            "/(marketing)/about",
+            "/(app)/about",
+            "/docs/[...path]",
+            "/docs/[section]/[page]",
+            "/docs/[[...all]]",
+            "/shop/[item]",
+            "/shop/cart",
+        ] {
+            router
+                .add_route(RouteDefinition::new(pattern.to_string(), "s".to_string(), "C".to_string(), None, false))
+                .unwrap();
+        }
+
+        let conflicts = router.route_conflicts();
+        let pair = |winner: &str, shadowed: &str| {
+            conflicts
+                .iter()
+                .find(|c| c.winner == winner && c.shadowed == shadowed)
+                .map(|c| c.kind)
+        };
+        assert_eq!(pair("/blog/[slug]", "/blog/[id]"), Some(ConflictKind::Ambiguous));
+        assert_eq!(pair("/(marketing)/about", "/(app)/about"), Some(ConflictKind::Ambiguous));
+        // The optional catch-all also matches /docs, which [...path] does not
+        assert_eq!(pair("/docs/[...path]", "/docs/[[...all]]"), None);
+        // A more specific route that covers only part of a broader one is fine
+        assert_eq!(pair("/docs/[section]/[page]", "/docs/[...path]"), None);
+        assert_eq!(pair("/shop/cart", "/shop/[item]"), None);
+        assert_eq!(conflicts.len(), 2);
+
+        let mut router = AeonRouter::new();
+        for pattern in ["/a/[x]", "/a/[...rest]", "/a/b"] {
+            router
+                .add_route(RouteDefinition::new(pattern.to_string(), "s".to_string(), "C".to_string(), None, false))
+                .unwrap();
+        }
+        // Specificity, not registration order, ranks /a/b above /a/[x]
+        assert_eq!(router.route_conflicts().len(), 0);
+        let json: Vec<serde_json::Value> = serde_json::from_str(&router.conflicts()).unwrap();
+        assert!(json.is_empty());
+    }
+
+    #[test]
+    fn test_path_normalization() {
+        let mut router = AeonRouter::new();
+        for (pattern, session) in [("/blog/[slug]", "blog-$slug"), ("/about", "about"), ("/files/[...path]", "files")] {
+            router
+                .add_route(RouteDefinition::new(pattern.to_string(), session.to_string(), "C".to_string(), None, false))
+                .unwrap();
+        }
+
+        let m = router.match_route("/blog/hello%20world").unwrap();
+        assert_eq!(m.get_param("slug"), Some("hello world".to_string()));
+        assert_eq!(m.resolved_session_id(), "blog-hello world");
+        assert_eq!(m.normalized_path(), "/blog/hello%20world");
+
+        // An encoded slash stays inside its segment
+        let m = router.match_route("/blog/a%2Fb").unwrap();
+        assert_eq!(m.get_param("slug"), Some("a/b".to_string()));
+        assert_eq!(m.normalized_path(), "/blog/a%2Fb");
+
+        assert_eq!(router.match_route("//about").unwrap().normalized_path(), "/about");
+        assert_eq!(router.match_route("/./about").unwrap().normalized_path(), "/about");
+        assert_eq!(router.match_route("/blog/../about/").unwrap().normalized_path(), "/about");
+        assert_eq!(
+            router.match_route("/files/a/./b/../c").unwrap().get_param("path"),
+            Some("a/c".to_string())
+        );
+
+        assert!(router.match_route("/About").is_none());
+        router.set_case_sensitive(false);
+        assert_eq!(router.match_route("/About").unwrap().normalized_path(), "/About");
+        assert_eq!(router.match_route("/BLOG/Post").unwrap().get_param("slug"), Some("Post".to_string()));
+    }
+
+    #[test]
+    fn test_trailing_slash_policy() {
+        let mut router = AeonRouter::new();
+        router
+            .add_route(RouteDefinition::new("/about".to_string(), "about".to_string(), "About".to_string(), None, false))
+            .unwrap();
+        router
+            .add_route(RouteDefinition::new("/".to_string(), "home".to_string(), "Home".to_string(), None, false))
+            .unwrap();
+
+        assert!(router.has_route("/about/"));
+
+        router.set_trailing_slash(TrailingSlash::Never);
+        assert!(router.has_route("/about"));
+        assert!(!router.has_route("/about/"));
+        assert!(router.has_route("/"));
+
+        router.set_trailing_slash(TrailingSlash::Always);
+        assert!(!router.has_route("/about"));
+        assert_eq!(router.match_route("/about/").unwrap().normalized_path(), "/about/");
+        assert_eq!(router.match_route("/").unwrap().normalized_path(), "/");
+    }
+
+    #[test]
+    fn test_query_and_fragment() {
+        let mut router = AeonRouter::new();
+        router
+            .add_route(RouteDefinition::new("/search".to_string(), "search-$?q".to_string(), "Search".to_string(), None, false))
+            .unwrap();
+        router
+            .add_route(RouteDefinition::new("/blog/[slug]".to_string(), "blog-$slug-$?page".to_string(), "Blog".to_string(), None, false))
+            .unwrap();
+
+        let m = router.match_route("/search?q=rust&tag=a&tag=b#top").unwrap();
+        assert_eq!(m.resolved_session_id(), "search-rust");
+        assert_eq!(m.get_query("q"), Some("rust".to_string()));
+        assert_eq!(m.get_query_all("tag"), r#"["a","b"]"#);
+        assert_eq!(m.get_query("missing"), None);
+        assert_eq!(m.fragment(), Some("top".to_string()));
+        assert_eq!(m.normalized_path(), "/search");
+
+        let m = router.match_route("/search/?q=hello+world").unwrap();
+        assert_eq!(m.resolved_session_id(), "search-hello world");
+
+        let m = router.match_route("/blog/post").unwrap();
+        assert_eq!(m.resolved_session_id(), "blog-post-");
+        assert_eq!(m.query_json(), "{}");
+        assert_eq!(m.fragment(), None);
+    }
+
+    #[test]
+    fn test_param_constraints() {
+        let mut router = AeonRouter::new();
+        for (pattern, session) in [
+            ("/users/[name]", "user-name"),
+            ("/users/[id:int]", "user-id"),
+            ("/files/[id:uuid]", "file"),
+            ("/[lang:(en|de|fr)]/about", "about-$lang"),
+            ("/tags/[slug:/^[a-z-]+$/]", "tag-$slug"),
+            ("/tags/[other]", "tag-other"),
+        ] {
+            router
+                .add_route(RouteDefinition::new(pattern.to_string(), session.to_string(), "C".to_string(), None, false))
+                .unwrap();
+        }
+
+        // Constrained routes outrank unconstrained ones and fall through on failure
+        assert_eq!(router.match_route("/users/42").unwrap().resolved_session_id(), "user-id");
+        assert_eq!(router.match_route("/users/-7").unwrap().resolved_session_id(), "user-id");
+        assert_eq!(router.match_route("/users/settings").unwrap().resolved_session_id(), "user-name");
+
+        assert!(router.has_route("/files/123e4567-e89b-12d3-a456-426614174000"));
+        assert!(!router.has_route("/files/123e4567e89b12d3a456426614174000"));
+
+        assert_eq!(router.match_route("/de/about").unwrap().resolved_session_id(), "about-de");
+        assert!(!router.has_route("/es/about"));
+
+        assert_eq!(router.match_route("/tags/rust-lang").unwrap().resolved_session_id(), "tag-rust-lang");
+        assert_eq!(router.match_route("/tags/Rust").unwrap().resolved_session_id(), "tag-other");
+    }
+
+    #[test]
+    fn test_constraint_validation_and_conflicts() {
+        for pattern in ["/[id:float]", "/[id:]", "/[x:/(/]", "/[x:()]", "/[:int]"] {
+            let error = parse_pattern(pattern).unwrap_err();
+            let expected = if pattern == "/[:int]" {
+                RouteErrorKind::EmptyParamName
+            } else {
+                RouteErrorKind::InvalidConstraint
+            };
+            assert_eq!(error.kind(), expected, "{}", pattern);
+        }
+        assert_eq!(
+            parse_pattern("/[slug:/^a\\/b$/]/x").unwrap().segments.len(),
+            2,
+            "escaped slash inside a regex stays in the segment"
+        );
+
+        let mut router = AeonRouter::new();
+        for pattern in ["/v/[id:int]", "/v/[n:int]", "/v/[lang:(en|de)]", "/v/[any]", "/v/42"] {
+            router
+                .add_route(RouteDefinition::new(pattern.to_string(), "s".to_string(), "C".to_string(), None, false))
+                .unwrap();
+        }
+        let conflicts: Vec<(String, String, ConflictKind)> = router
+            .route_conflicts()
+            .into_iter()
+            .map(|c| (c.winner, c.shadowed, c.kind))
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec![("/v/[id:int]".to_string(), "/v/[n:int]".to_string(), ConflictKind::Ambiguous)]
+        );
+    }
+
+    #[test]
+    fn test_url_for() {
+        let mut router = AeonRouter::new();
+        for (pattern, component) in [
+            ("/", "Home"),
+            ("/(blog)/blog/[slug]", "BlogPost"),
+            ("/users/[id:int]", "User"),
+            ("/api/[...path]", "Api"),
+            ("/docs/[[...slug]]", "Docs"),
+        ] {
+            router
+                .add_route(RouteDefinition::new(pattern.to_string(), "s".to_string(), component.to_string(), None, false))
+                .unwrap();
+        }
+
+        assert_eq!(router.url_for("Home", "{}").unwrap(), "/");
+        assert_eq!(router.url_for("BlogPost", r#"{"slug": "hello world"}"#).unwrap(), "/blog/hello%20world");
+        assert_eq!(router.url_for("/users/[id:int]", r#"{"id": 42}"#).unwrap(), "/users/42");
+        assert_eq!(router.url_for("Api", r#"{"path": ["v1", "a/b"]}"#).unwrap(), "/api/v1/a%2Fb");
+        assert_eq!(router.url_for("Api", r#"{"path": "v1/users"}"#).unwrap(), "/api/v1/users");
+        assert_eq!(router.url_for("Docs", "").unwrap(), "/docs");
+        assert_eq!(router.url_for("Docs", r#"{"slug": []}"#).unwrap(), "/docs");
+        assert_eq!(router.url_for("Docs", r#"{"slug": ["a", "b"]}"#).unwrap(), "/docs/a/b");
+
+        // Round-trips through match_route
+        let url = router.url_for("BlogPost", r#"{"slug": "a/b?c"}"#).unwrap();
+        assert_eq!(router.match_route(&url).unwrap().get_param("slug"), Some("a/b?c".to_string()));
+
+        let kind = |route: &str, params: &str| router.url_for(route, params).unwrap_err().kind();
+        assert_eq!(kind("Missing", "{}"), RouteErrorKind::UnknownRoute);
+        assert_eq!(kind("BlogPost", "{}"), RouteErrorKind::MissingParam);
+        assert_eq!(kind("Api", r#"{"path": []}"#), RouteErrorKind::MissingParam);
+        assert_eq!(kind("BlogPost", r#"{"slug": "a", "page": 2}"#), RouteErrorKind::UnexpectedParam);
+        assert_eq!(kind("User", r#"{"id": "abc"}"#), RouteErrorKind::InvalidParamValue);
+        assert_eq!(kind("User", r#"{"id": [1]}"#), RouteErrorKind::InvalidParamValue);
+        assert_eq!(kind("User", "not json"), RouteErrorKind::InvalidParamValue);
+
+        let error = router.url_for("BlogPost", "{}").unwrap_err();
+        assert_eq!(error.segment(), "[slug]");
+        assert_eq!(error.pattern(), "/(blog)/blog/[slug]");
+    }
+
+    #[test]
+    fn test_named_routes_and_metadata() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/blog/[slug]", "sessionId": "blog-$slug", "componentId": "BlogPost",
+                 "name": "blog.post", "metadata": {"cacheTtl": 300, "auth": false, "prerender": true}},
+                {"pattern": "/account", "sessionId": "account", "componentId": "Account",
+                 "name": "account", "metadata": {"auth": true}},
+                {"pattern": "/other", "sessionId": "other", "componentId": "Other", "name": "account"},
+                {"pattern": "/bad", "sessionId": "bad", "componentId": "Bad", "metadata": [1, 2]}
+            ]"#,
+        );
+        assert_eq!(report.loaded(), 2);
+        let errors: Vec<serde_json::Value> = serde_json::from_str(&report.errors_json()).unwrap();
+        assert_eq!(errors[0]["kind"], "DuplicateRouteName");
+        assert_eq!(errors[1]["index"], 3);
+
+        let m = router.match_route("/blog/hello").unwrap();
+        assert_eq!(m.name(), Some("blog.post".to_string()));
+        assert_eq!(m.get_metadata("cacheTtl"), Some("300".to_string()));
+        assert_eq!(m.get_metadata("missing"), None);
+        let metadata: serde_json::Value = serde_json::from_str(&m.metadata_json()).unwrap();
+        assert_eq!(metadata["prerender"], true);
+
+        assert_eq!(router.get_by_name("account").unwrap().pattern(), "/account");
+        assert!(router.get_by_name("nope").is_none());
+        assert_eq!(router.url_for("blog.post", r#"{"slug": "x"}"#).unwrap(), "/blog/x");
+
+        let mut definition = RouteDefinition::new("/about".to_string(), "about".to_string(), "About".to_string(), None, false);
+        assert_eq!(definition.metadata_json(), "{}");
+        definition.set_name(Some("about".to_string()));
+        definition.set_metadata_json(r#"{"revalidate": 60}"#).unwrap();
+        router.add_route(definition).unwrap();
+        assert_eq!(router.match_route("/about").unwrap().get_metadata("revalidate"), Some("60".to_string()));
+    }
+
+    #[test]
+    fn test_layout_chain() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"{
+                "routes": [
+                    {"pattern": "/(dashboard)/settings/profile", "sessionId": "p", "componentId": "Profile", "layout": "ProfileLayout"},
+                    {"pattern": "/(dashboard)/settings", "sessionId": "s", "componentId": "Settings", "layout": "SettingsLayout"},
+                    {"pattern": "/(marketing)/about", "sessionId": "a", "componentId": "About"}
+                ],
+                "layouts": {"/": "RootLayout", "/(dashboard)": "DashboardLayout"}
+            }"#,
+        );
+        assert!(report.is_ok());
+        router.add_layout("(dashboard)/settings/", "SettingsLayout");
+
+        let chain = |path: &str| -> Vec<String> {
+            serde_json::from_str(&router.match_route(path).unwrap().layouts_json()).unwrap()
+        };
+        assert_eq!(
+            chain("/settings/profile"),
+            vec!["RootLayout", "DashboardLayout", "SettingsLayout", "ProfileLayout"]
+        );
+        // The route's own layout is not repeated when registered for its directory
+        assert_eq!(chain("/settings"), vec!["RootLayout", "DashboardLayout", "SettingsLayout"]);
+        assert_eq!(chain("/about"), vec!["RootLayout"]);
+    }
+
+    #[test]
+    fn test_parallel_and_intercepting_routes() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/", "sessionId": "home", "componentId": "Home"},
+                {"pattern": "/feed", "sessionId": "feed", "componentId": "Feed"},
+                {"pattern": "/photo/[id]", "sessionId": "photo-$id", "componentId": "Photo"},
+                {"pattern": "/@sidebar/[[...path]]", "sessionId": "sidebar", "componentId": "Sidebar"},
+                {"pattern": "/@modal/(.)photo/[id]", "sessionId": "modal-$id", "componentId": "PhotoModal"},
+                {"pattern": "/feed/(..)photo/[id]", "sessionId": "feed-photo-$id", "componentId": "FeedPhoto"}
+            ]"#,
+        );
+        assert!(report.is_ok(), "{}", report.errors_json());
+
+        let m = router.match_route("/photo/7").unwrap();
+        assert_eq!(m.resolved_session_id(), "photo-7");
+        assert!(m.slot().is_none());
+        assert_eq!(m.slot_names_json(), r#"["sidebar"]"#);
+        assert_eq!(m.get_slot("sidebar").unwrap().get_param("path"), Some("photo/7".to_string()));
+        assert!(m.intercepted().is_none());
+
+        // Every referrer is under the modal's root-level interceptor; the
+        // interceptor declared under /feed wins for referrers below /feed
+        let from_home = router.match_route_from("/photo/7", "/").unwrap();
+        let intercepted = from_home.intercepted().unwrap();
+        assert_eq!(intercepted.resolved_session_id(), "modal-7");
+        assert_eq!(intercepted.slot(), Some("modal".to_string()));
+
+        let from_feed = router.match_route_from("/photo/7", "/feed?tab=new").unwrap();
+        assert_eq!(from_feed.resolved_session_id(), "photo-7");
+        assert_eq!(from_feed.intercepted().unwrap().resolved_session_id(), "feed-photo-7");
+
+        // Slot and intercepting routes never match on their own
+        assert!(router.match_route("/@modal/photo/7").is_none());
+        assert_eq!(router.match_route("/feed").unwrap().resolved_session_id(), "feed");
+        assert_eq!(router.url_for("/feed/(..)photo/[id]", r#"{"id": 3}"#).unwrap(), "/photo/3");
+        assert_eq!(router.route_conflicts(), Vec::new());
+
+        for (pattern, kind) in [
+            ("/@", RouteErrorKind::InvalidSlot),
+            ("/@a/@b/x", RouteErrorKind::InvalidSlot),
+            ("/(..)photo", RouteErrorKind::InvalidInterceptor),
+            ("/a/(.)", RouteErrorKind::InvalidInterceptor),
+            ("/a/(.)b/(.)c", RouteErrorKind::InvalidInterceptor),
+        ] {
+            assert_eq!(parse_pattern(pattern).unwrap_err().kind(), kind, "{}", pattern);
+        }
+        let parsed = parse_pattern("/[team]/feed/(..)(..)photo/[team]").unwrap();
+        assert_eq!(parsed.segments.len(), 2);
+        assert_eq!(parsed.intercept_from.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_locale_routing() {
+        let mut router = AeonRouter::new();
+        router.add_route(RouteDefinition::new("/".into(), "home-$locale".into(), "Home".into(), None, false)).unwrap();
+        router
+            .add_route(RouteDefinition::new("/blog/[slug]".into(), "blog-$locale-$slug".into(), "Post".into(), None, false))
+            .unwrap();
+
+        // Without locales the prefix is just a path segment
+        assert!(router.match_route("/de/blog/x").is_none());
+        assert_eq!(router.match_route("/blog/x").unwrap().locale(), None);
+
+        router.set_locales(vec!["en".into(), "de".into(), "fr-CA".into()], "en");
+        let m = router.match_route("/DE/blog/x?p=1").unwrap();
+        assert_eq!(m.locale().as_deref(), Some("de"));
+        assert_eq!(m.resolved_session_id(), "blog-de-x");
+        assert_eq!(m.normalized_path(), "/DE/blog/x");
+        assert_eq!(router.match_route("/blog/x").unwrap().resolved_session_id(), "blog-en-x");
+        assert_eq!(router.match_route("/fr-ca").unwrap().resolved_session_id(), "home-fr-CA");
+        assert!(router.match_route("/it/blog/x").is_none());
+
+        assert_eq!(router.negotiate_locale("it, de-AT;q=0.8, en;q=0.5").as_deref(), Some("de"));
+        assert_eq!(router.negotiate_locale("fr;q=0.9, en;q=0.9").as_deref(), Some("fr-CA"));
+        assert_eq!(router.negotiate_locale("de;q=0, *").as_deref(), Some("en"));
+        assert_eq!(router.negotiate_locale("").as_deref(), Some("en"));
+
+        assert_eq!(router.url_for_locale("/blog/[slug]", r#"{"slug": "x"}"#, "de").unwrap(), "/de/blog/x");
+        assert_eq!(router.url_for_locale("/blog/[slug]", r#"{"slug": "x"}"#, "EN").unwrap(), "/blog/x");
+        assert_eq!(router.url_for_locale("/", "", "fr-ca").unwrap(), "/fr-CA");
+        assert_eq!(router.url_for_locale("/", "", "it").unwrap_err().kind(), RouteErrorKind::UnknownLocale);
+    }
+
+    #[test]
+    fn test_host_routing() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/[...path]", "sessionId": "any", "componentId": "Fallback"},
+                {"pattern": "/dashboard", "sessionId": "wild-dash", "componentId": "Wild", "host": "*.example.com"},
+                {"pattern": "/dashboard", "sessionId": "dash-$tenant", "componentId": "Tenant", "host": "[tenant].example.com"},
+                {"pattern": "/dashboard", "sessionId": "admin", "componentId": "Admin", "host": "admin.example.com"},
+                {"pattern": "/[page]", "sessionId": "$tenant-$page", "componentId": "Page", "host": "[tenant].example.com"},
+                {"pattern": "/dashboard", "sessionId": "dup", "componentId": "Dup", "host": "admin.example.com"},
+                {"pattern": "/[tenant]", "sessionId": "x", "componentId": "X", "host": "[tenant].example.com"},
+                {"pattern": "/x", "sessionId": "x", "componentId": "X", "host": "bad_host.example.com"}
+            ]"#,
+        );
+        assert_eq!(report.loaded(), 5);
+        assert_eq!(report.error_count(), 3, "{}", report.errors_json());
+
+        let session = |host: &str, path: &str| router.match_route_with_host(host, path).unwrap().resolved_session_id();
+        assert_eq!(session("acme.example.com:8443", "/dashboard"), "dash-acme");
+        assert_eq!(session("ADMIN.example.com.", "/dashboard"), "admin");
+        // A host route beats a more specific-looking path without a host
+        assert_eq!(session("acme.example.com", "/settings"), "acme-settings");
+        assert_eq!(
+            router.match_route_with_host("acme.example.com", "/settings").unwrap().get_param("tenant").as_deref(),
+            Some("acme")
+        );
+        assert_eq!(session("example.com", "/dashboard"), "any");
+        assert_eq!(session("a.b.example.com", "/dashboard"), "any");
+        // Without a host only host-less routes match
+        assert_eq!(router.match_route("/dashboard").unwrap().resolved_session_id(), "any");
+        assert_eq!(router.route_conflicts(), Vec::new());
+    }
+
+    #[test]
+    fn test_route_lookup_by_shared_pattern() {
+        let mut router = AeonRouter::new();
+        router.load_manifest(
+            r#"[
+                {"pattern": "/dashboard", "sessionId": "wild", "componentId": "Wild", "host": "*.example.com"},
+                {"pattern": "/dashboard", "sessionId": "admin", "componentId": "Admin", "host": "admin.example.com"}
+            ]"#,
+        );
+        let component = |router: &AeonRouter| router.find_route("/dashboard").map(|r| r.definition.component_id());
+        assert_eq!(component(&router).as_deref(), Some("Admin"));
+        router.remove_route("/dashboard").unwrap();
+        assert_eq!(component(&router).as_deref(), Some("Wild"));
+    }
+
+    #[test]
+    fn test_method_matching() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/api/[...path]", "sessionId": "api", "componentId": "Api"},
+                {"pattern": "/api/[...path]", "sessionId": "api-post", "componentId": "ApiPost", "methods": ["post"]},
+                {"pattern": "/users/[id]", "sessionId": "user", "componentId": "User", "methods": ["GET", "DELETE"]},
+                {"pattern": "/users/[id]/x", "sessionId": "x", "componentId": "X", "methods": []},
+                {"pattern": "/users/[id]/y", "sessionId": "y", "componentId": "Y", "methods": ["G ET"]}
+            ]"#,
+        );
+        assert_eq!(report.error_count(), 2, "{}", report.errors_json());
+        assert_eq!(report.loaded(), 3);
+
+        let session = |method: &str, path: &str| {
+            let result = router.match_route_with_method(method, path);
+            assert_eq!(result.status(), MethodMatchStatus::Matched);
+            result.route_match().unwrap().resolved_session_id()
+        };
+        assert_eq!(session("POST", "/api/a/b"), "api-post");
+        assert_eq!(session("get", "/api/a/b"), "api");
+        assert_eq!(session("HEAD", "/users/1"), "user");
+        assert_eq!(session("delete", "/users/1"), "user");
+
+        // PUT falls through to the catch-all under /api, but not here
+        assert_eq!(session("PUT", "/api/users/1"), "api");
+        let result = router.match_route_with_method("PUT", "/users/1");
+        assert_eq!(result.status(), MethodMatchStatus::MethodNotAllowed);
+        assert_eq!(result.allow_header(), "DELETE, GET, HEAD");
+        assert!(result.route_match().is_none());
+        assert_eq!(router.match_route_with_method("GET", "/other").status(), MethodMatchStatus::NotFound);
+
+        // Method-agnostic matching and conflicts
+        assert_eq!(router.match_route("/api/a").unwrap().resolved_session_id(), "api");
+        assert_eq!(router.route_conflicts(), Vec::new());
+    }
+
+    #[test]
+    fn test_match_all_and_explain() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/users/[id:int]", "sessionId": "user-$id", "componentId": "User"},
+                {"pattern": "/users/me", "sessionId": "me", "componentId": "Me"},
+                {"pattern": "/users/[id]/posts", "sessionId": "posts", "componentId": "Posts"},
+                {"pattern": "/[...rest]", "sessionId": "rest", "componentId": "Rest"},
+                {"pattern": "/users", "sessionId": "users", "componentId": "Users", "host": "app.example.com"}
+            ]"#,
+        );
+        assert!(report.is_ok());
+
+        let all: Vec<serde_json::Value> = serde_json::from_str(&router.match_all("/users/42")).unwrap();
+        let sessions: Vec<&str> = all.iter().map(|m| m["resolved_session_id"].as_str().unwrap()).collect();
+        assert_eq!(sessions, vec!["user-42", "rest"]);
+        assert_eq!(router.match_all("/nothing/.."), "[]");
+
+        let explanation: serde_json::Value = serde_json::from_str(&router.explain("/users/abc")).unwrap();
+        assert_eq!(explanation["matched"], "/[...rest]");
+        assert_eq!(explanation["segments"], serde_json::json!(["users", "abc"]));
+        let reasons: HashMap<String, serde_json::Value> = explanation["routes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| (r["pattern"].as_str().unwrap().to_string(), r["mismatch"].clone()))
+            .collect();
+        assert_eq!(
+            reasons["/users/me"],
+            serde_json::json!({"reason": "StaticMismatch", "segment_index": 1, "expected": "me", "actual": "abc"})
+        );
+        assert_eq!(reasons["/users/[id:int]"]["reason"], "ConstraintFailure");
+        assert_eq!(reasons["/users/[id]/posts"]["reason"], "MissingSegment");
+        assert_eq!(reasons["/users/[id]/posts"]["expected"], "posts");
+        assert_eq!(reasons["/users"]["reason"], "HostRequired");
+        assert!(reasons["/[...rest]"].is_null());
+
+        let explanation: serde_json::Value = serde_json::from_str(&router.explain("/users/me/x/y")).unwrap();
+        let me = explanation["routes"].as_array().unwrap().iter().find(|r| r["pattern"] == "/users/me").unwrap();
+        assert_eq!(me["mismatch"]["reason"], "LeftoverSegments");
+        assert_eq!(me["mismatch"]["actual"], "x");
+
+        router.set_trailing_slash(TrailingSlash::Never);
+        let explanation: serde_json::Value = serde_json::from_str(&router.explain("/users/me/")).unwrap();
+        assert!(explanation["rejected"].is_string());
+        assert!(explanation["matched"].is_null());
+        assert_eq!(router.match_all("/users/me/"), "[]");
+    }
+
+    #[test]
+    fn test_session_templates() {
+        let mut router = AeonRouter::new();
+        let add = |router: &mut AeonRouter, pattern: &str, session: &str| {
+            router.add_route(RouteDefinition::new(pattern.into(), session.into(), "C".into(), None, false))
+        };
+        add(&mut router, "/items/[id]/[idx]", "item-$id-$idx").unwrap();
+        add(&mut router, "/docs/[...path]", "docs-${path|lowercase}").unwrap();
+        add(&mut router, "/t/[tenant]", "${tenant|slugify}").unwrap();
+        assert_eq!(add(&mut router, "/x/[id]", "x-$idx").unwrap_err().kind(), RouteErrorKind::UnknownTemplateParam);
+        assert_eq!(add(&mut router, "/y/[id]", "y-${id|upper}").unwrap_err().kind(), RouteErrorKind::InvalidTemplate);
+        assert!(router.match_route("/x/1").is_none());
+
+        assert_eq!(router.match_route("/items/1/$id").unwrap().resolved_session_id(), "item-1-$id");
+        assert_eq!(router.match_route("/t/Acme%20Corp").unwrap().resolved_session_id(), "acme-corp");
+        assert_eq!(router.match_route("/docs/API/Auth").unwrap().resolved_session_id(), "docs-api/auth");
+        router.set_session_escape("/", Some("~".into()));
+        assert_eq!(router.match_route("/docs/API/Auth").unwrap().resolved_session_id(), "docs-api~auth");
+    }
+
+    #[test]
+    fn test_route_mutation() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/blog/[slug]", "sessionId": "a-$slug", "componentId": "A", "name": "a"},
+                {"pattern": "/blog/[id]", "sessionId": "b-$id", "componentId": "B", "name": "b"},
+                {"pattern": "/about", "sessionId": "about", "componentId": "About"}
+            ]"#,
+        );
+        assert!(report.is_ok());
+        assert_eq!(router.version(), 3);
+        let session = |router: &AeonRouter, path: &str| router.match_route(path).map(|m| m.resolved_session_id());
+
+        // Replacing keeps registration order, so "a" still wins the tie
+        let mut replacement = RouteDefinition::new("/blog/[post]".into(), "c-$post".into(), "C".into(), None, false);
+        replacement.set_name(Some("a".into()));
+        assert_eq!(router.replace_route("a", replacement).unwrap(), 4);
+        assert_eq!(session(&router, "/blog/x").as_deref(), Some("c-x"));
+        assert_eq!(router.get_by_name("a").unwrap().component_id(), "C");
+
+        let mut clash = RouteDefinition::new("/x".into(), "x".into(), "X".into(), None, false);
+        clash.set_name(Some("b".into()));
+        assert_eq!(router.replace_route("About", clash).unwrap_err().kind(), RouteErrorKind::DuplicateRouteName);
+
+        assert_eq!(router.remove_route("/blog/[post]").unwrap(), 5);
+        assert_eq!(session(&router, "/blog/x").as_deref(), Some("b-x"));
+        assert!(router.get_by_name("a").is_none());
+        assert_eq!(router.get_by_name("b").unwrap().component_id(), "B");
+        assert_eq!(router.remove_route("/nope").unwrap_err().kind(), RouteErrorKind::UnknownRoute);
+
+        // A failing diff leaves the router untouched
+        let error = router
+            .apply_route_diff(r#"[{"pattern": "/new", "sessionId": "$missing", "componentId": "N"}]"#, r#"["About"]"#)
+            .unwrap_err();
+        assert_eq!(error.kind(), RouteErrorKind::UnknownTemplateParam);
+        assert_eq!(router.version(), 5);
+        assert!(router.has_route("/about"));
+
+        let version = router
+            .apply_route_diff(r#"[{"pattern": "/new", "sessionId": "new", "componentId": "N"}]"#, r#"["About", "b"]"#)
+            .unwrap();
+        assert_eq!(version, 6);
+        assert!(!router.has_route("/about"));
+        assert!(!router.has_route("/blog/x"));
+        assert_eq!(session(&router, "/new").as_deref(), Some("new"));
+        assert_eq!(router.apply_route_diff("{}", "[]").unwrap_err().kind(), RouteErrorKind::InvalidRouteDiff);
+    }
+
+    #[test]
+    fn test_evaluate_rules() {
+        let mut router = AeonRouter::new();
+        router.add_route(RouteDefinition::new("/blog/[slug]".into(), "blog-$slug".into(), "Post".into(), None, false)).unwrap();
+        router.add_route(RouteDefinition::new("/docs/[...path]".into(), "docs".into(), "Docs".into(), None, false)).unwrap();
+        let report = router.load_rules(
+            r#"[
+                {"source": "/beta/[slug]", "destination": "/blog/$slug", "kind": "rewrite",
+                 "has": [{"type": "cookie", "key": "beta", "value": "on"}]},
+                {"source": "/old/[slug]", "destination": "/blog/$slug", "kind": "redirect", "permanent": true},
+                {"source": "/manual/[...path]", "destination": "/docs/$path", "kind": "rewrite"},
+                {"source": "/guide/[...path]", "destination": "/manual/$path", "kind": "rewrite"},
+                {"source": "/ping", "destination": "/pong", "kind": "rewrite"},
+                {"source": "/pong", "destination": "/ping", "kind": "rewrite"},
+                {"source": "/bad/[id]", "destination": "/x/$slug", "kind": "redirect"},
+                {"source": "/bad", "destination": "https://example.com", "kind": "rewrite"}
+            ]"#,
+        );
+        assert_eq!(report.loaded(), 6);
+        assert_eq!(report.error_count(), 2);
+        assert!(report.errors_json().contains("InvalidDestination"));
+
+        let outcome = router.evaluate("/old/hello%20world?ref=x", "").unwrap();
+        assert_eq!(outcome.kind(), OutcomeKind::Redirect);
+        assert_eq!(outcome.status(), Some(308));
+        assert_eq!(outcome.location().as_deref(), Some("/blog/hello%20world?ref=x"));
+
+        // Rewrites chain and keep the query; the match is for the final path
+        let outcome = router.evaluate("/guide/a/b?v=2", "").unwrap();
+        assert_eq!(outcome.kind(), OutcomeKind::Rewrite);
+        assert_eq!(outcome.path(), "/docs/a/b?v=2");
+        let route_match = outcome.route_match().unwrap();
+        assert_eq!(route_match.get_param("path").as_deref(), Some("a/b"));
+        assert_eq!(route_match.get_query("v").as_deref(), Some("2"));
+
+        // Conditions
+        assert_eq!(router.evaluate("/beta/x", "").unwrap().kind(), OutcomeKind::NotFound);
+        let request = r#"{"headers": {"Cookie": "beta=on"}}"#;
+        assert_eq!(router.evaluate("/beta/x", request).unwrap().kind(), OutcomeKind::Rewrite);
+
+        assert_eq!(router.evaluate("/blog/x", "").unwrap().kind(), OutcomeKind::Match);
+        assert_eq!(router.evaluate("/ping", "").unwrap_err().kind(), RouteErrorKind::RewriteLoop);
+        assert_eq!(router.evaluate("/ping", "{").unwrap_err().kind(), RouteErrorKind::InvalidRequest);
+    }
+
+    /// Split a URL path into its non-empty segments (the pre-normalization behavior)
+    fn split_path(path: &str) -> Vec<&str> {
+        path.trim_start_matches('/')
+            .trim_end_matches('/')
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// The pre-trie matcher: every route in a Vec sorted by specificity
+    struct LinearRouter {
+        routes: Vec<(Vec<Segment>, RouteDefinition)>,
+    }
+
+    impl LinearRouter {
+        fn new(definitions: &[RouteDefinition]) -> Self {
+            let mut routes: Vec<(Vec<Segment>, RouteDefinition)> = definitions
+                .iter()
+                .map(|d| (parse_pattern(&d.pattern()).unwrap().segments, d.clone()))
+                .collect();
+            routes.sort_by_key(|r| std::cmp::Reverse(route_specificity(&r.0)));
+            Self { routes }
+        }
+
+        fn match_route(&self, path: &str) -> Option<RouteMatch> {
+            let path_segments = split_path(path);
+            self.routes.iter().find_map(|(segments, definition)| {
+                match_segments(segments, &path_segments, true).map(|params| RouteMatch {
+                    resolved_session_id: SessionTemplate::parse(&definition.session_id(), &[]).unwrap().render(
+                        &params,
+                        &HashMap::new(),
+                        None,
+                        &SeparatorEscape::default(),
+                    ),
+                    route: definition.clone(),
+                    params,
+                    layouts: Vec::new(),
+                    normalized_path: String::new(),
+                    query: HashMap::new(),
+                    fragment: None,
+                    locale: None,
+                    slot: None,
+                    slots: HashMap::new(),
+                    intercepted: None,
+                })
+            })
+        }
+    }
+
+    /// Deterministic route table mixing every segment kind at several depths
+    fn generated_manifest(count: usize) -> Vec<RouteDefinition> {
+        let statics = ["blog", "docs", "api", "shop", "users", "settings", "en", "de"];
+        (0..count)
+            .map(|i| {
+                let mut pattern = String::new();
+                let depth = 1 + i % 4;
+                for d in 0..depth {
+                    let pick = (i / (d + 1) + d * 7) % 11;
+                    let segment = match pick {
+                        0..=5 => statics[(i + d) % statics.len()].to_string(),
+                        6 => format!("s{}", i % 50),
+                        7 | 8 => format!("[p{}]", d),
+                        9 if d == depth - 1 => format!("[...rest{}]", d),
+                        10 if d == depth - 1 => format!("[[...opt{}]]", d),
+                        _ => format!("[q{}]", d),
+                    };
+                    pattern.push('/');
+                    pattern.push_str(&segment);
+                }
+                RouteDefinition::new(pattern, format!("route-{}", i), format!("C{}", i), None, false)
+            })
+            .collect()
+    }
+
+    fn generated_paths() -> Vec<String> {
+        let words = ["blog", "docs", "api", "shop", "users", "settings", "en", "de", "s7", "s42", "x", "123"];
+        let mut paths = vec!["/".to_string(), "".to_string()];
+        for a in words {
+            paths.push(format!("/{}", a));
+            for b in words {
+                paths.push(format!("/{}/{}", a, b));
+                for c in ["blog", "x", "s7", "de"] {
+                    paths.push(format!("/{}/{}/{}", a, b, c));
+                    paths.push(format!("/{}/{}/{}/y/z", a, b, c));
+                }
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn test_trie_matches_linear_reference() {
+        let manifest = generated_manifest(2000);
+        let linear = LinearRouter::new(&manifest);
+        let mut router = AeonRouter::new();
+        for definition in &manifest {
+            router.add_route(definition.clone()).unwrap();
+        }
+
+        for path in generated_paths() {
+            let expected = linear.match_route(&path);
+            let actual = router.match_route(&path);
+            assert_eq!(
+                actual.map(|m| (m.route.pattern(), m.resolved_session_id, m.params)),
+                expected.map(|m| (m.route.pattern(), m.resolved_session_id, m.params)),
+                "mismatch for {}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_catch_all_precedence_matches_specificity() {
+        let mut router = AeonRouter::new();
+        router.add_route(RouteDefinition::new(
+            "/a/[...rest]".to_string(),
+            "rest".to_string(),
+            "Rest".to_string(),
+            None,
+            false,
+        )).unwrap();
+        router.add_route(RouteDefinition::new(
+            "/[x]/b/c".to_string(),
+            "xbc".to_string(),
+            "Xbc".to_string(),
+            None,
+            false,
+        )).unwrap();
+        router.add_route(RouteDefinition::new(
+            "/docs/[[...slug]]".to_string(),
+            "docs".to_string(),
+            "Docs".to_string(),
+            None,
+            false,
+        )).unwrap();
+
+        // A deeper dynamic route outranks a shallow static prefix with a catch-all
+        assert_eq!(router.match_route("/a/b/c").unwrap().resolved_session_id(), "xbc");
+        assert_eq!(router.match_route("/a/b/d").unwrap().resolved_session_id(), "rest");
+        assert_eq!(router.match_route("/docs").unwrap().resolved_session_id(), "docs");
+        assert!(router.match_route("/a").is_none());
+        assert_eq!(
+            router.get_routes_json(),
+            r#"["/[x]/b/c","/a/[...rest]","/docs/[[...slug]]"]"#
+        );
+    }
+
+    /// `cargo test --release -- --ignored --nocapture bench_` for timings
+    #[test]
+    #[ignore]
+    fn bench_trie_vs_linear() {
+        use std::time::Instant;
+
+        let manifest = generated_manifest(2000);
+        let paths = generated_paths();
+
+        let start = Instant::now();
+        let mut router = AeonRouter::new();
+        for definition in &manifest {
+            router.add_route(definition.clone()).unwrap();
+        }
+        let load = start.elapsed();
+
+        let start = Instant::now();
+        let trie_hits = paths.iter().filter(|p| router.match_route(p).is_some()).count();
+        let trie = start.elapsed();
+
+        let start = Instant::now();
+        let linear_router = LinearRouter::new(&manifest);
+        let linear_load = start.elapsed();
+
+        let start = Instant::now();
+        let linear_hits = paths.iter().filter(|p| linear_router.match_route(p).is_some()).count();
+        let linear = start.elapsed();
+
+        assert_eq!(trie_hits, linear_hits);
+        println!(
+            "{} routes / {} paths: trie load {:?} match {:?}, linear load {:?} match {:?}",
+            manifest.len(),
+            paths.len(),
+            load,
+            trie,
+            linear_load,
+            linear
+        );
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"{"routes": [
+                {"pattern": "/users/[id:int]", "sessionId": "user-${id|hash}", "componentId": "User",
+                 "name": "user", "methods": ["GET"], "metadata": {"ttl": 60}},
+                {"pattern": "/tags/[slug:/^[a-z-]+$/]", "sessionId": "tag-$slug", "componentId": "Tag"},
+                {"pattern": "/(shop)/[...path]", "sessionId": "shop", "componentId": "Shop",
+                 "host": "[tenant].example.com"},
+                {"pattern": "/@modal/login", "sessionId": "login", "componentId": "Login"},
+                {"pattern": "/photo/[id]", "sessionId": "photo-$id", "componentId": "Photo"},
+                {"pattern": "/feed/(..)photo/[id]", "sessionId": "photo-$id", "componentId": "Modal"}
+            ], "layouts": {"/(shop)": "ShopLayout"}}"#,
+        );
+        assert!(report.is_ok());
+        router.set_locales(vec!["en".into(), "de".into()], "en");
+        router.set_session_escape("/", Some("_".into()));
+        router.load_rules(r#"[{"source": "/old/[id]", "destination": "/users/$id", "kind": "redirect",
+            "has": [{"type": "cookie", "key": "beta"}]}]"#);
+
+        let bytes = router.to_bytes();
+        assert_eq!(&bytes[..4], b"AERT");
+        let loaded = AeonRouter::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.to_bytes(), bytes);
+        assert_eq!(loaded.version(), router.version());
+        assert_eq!(loaded.get_routes_json(), router.get_routes_json());
+        for (loaded, original) in loaded.routes.iter().zip(&router.routes) {
+            let derived = |r: &ParsedRoute| (r.specificity, r.host_specificity, r.table.clone(), r.directories.clone(), r.methods.clone());
+            assert_eq!(derived(loaded), derived(original));
+        }
+
+        let user = loaded.match_route("/de/users/7").unwrap();
+        assert_eq!(user.locale().as_deref(), Some("de"));
+        assert_eq!(user.resolved_session_id(), router.match_route("/users/7").unwrap().resolved_session_id());
+        assert!(loaded.match_route("/users/x").is_none());
+        assert!(loaded.match_route("/tags/rust-lang").is_some());
+        assert!(loaded.match_route("/tags/Rust").is_none());
+        let shop = loaded.match_route_with_host("acme.example.com", "/a/b").unwrap();
+        assert_eq!(shop.layouts, vec!["ShopLayout".to_string()]);
+        assert_eq!(loaded.match_route_with_method("POST", "/users/7").status(), MethodMatchStatus::MethodNotAllowed);
+        assert!(loaded.match_route_from("/photo/1", "/feed").unwrap().intercepted().is_some());
+        let outcome = loaded.evaluate("/old/3", r#"{"cookies": {"beta": "1"}}"#).unwrap();
+        assert_eq!(outcome.location().as_deref(), Some("/users/3"));
+
+        let error = |bytes: &[u8]| AeonRouter::from_bytes(bytes).err().map(|e| e.kind());
+        let mut corrupted = bytes.clone();
+        corrupted[10] ^= 0xff;
+        assert_eq!(error(&corrupted), Some(RouteErrorKind::InvalidSnapshot));
+        assert_eq!(error(&bytes[..20]), Some(RouteErrorKind::InvalidSnapshot));
+        let mut newer = bytes.clone();
+        newer[4] = newer[4].wrapping_add(1);
+        assert_eq!(error(&newer), Some(RouteErrorKind::UnsupportedSnapshot));
+    }
+
+    #[test]
+    fn test_speculation_rules() {
+        let mut router = AeonRouter::new();
+        let report = router.load_manifest(
+            r#"[
+                {"pattern": "/", "sessionId": "home", "componentId": "Home"},
+                {"pattern": "/about", "sessionId": "about", "componentId": "About",
+                 "metadata": {"speculation": "prefetch"}},
+                {"pattern": "/blog/[slug]", "sessionId": "blog-$slug", "componentId": "Post"},
+                {"pattern": "/blog/drafts", "sessionId": "drafts", "componentId": "Drafts",
+                 "metadata": {"speculation": false}},
+                {"pattern": "/api/[...path]", "sessionId": "api", "componentId": "Api", "methods": ["POST"]},
+                {"pattern": "/@modal/login", "sessionId": "login", "componentId": "Login"},
+                {"pattern": "/admin", "sessionId": "admin", "componentId": "Admin", "host": "admin.example.com"}
+            ]"#,
+        );
+        assert!(report.is_ok());
+        router.set_locales(vec!["en".into(), "de".into()], "en");
+        router.set_trailing_slash(TrailingSlash::Never);
+
+        let rules: serde_json::Value = serde_json::from_str(&router.speculation_rules(Eagerness::Moderate)).unwrap();
+        assert_eq!(rules["prerender"][0]["urls"], serde_json::json!(["/", "/de"]));
+        assert_eq!(rules["prefetch"][0]["urls"], serde_json::json!(["/about", "/de/about"]));
+        assert_eq!(
+            rules["prefetch"][1]["where"],
+            serde_json::json!({"and": [
+                {"or": [{"href_matches": "{/(en|de)}?/blog/:slug"}]},
+                {"not": {"or": [{"href_matches": "{/(en|de)}?/blog/drafts"}]}}
+            ]})
+        );
+        assert_eq!(rules["prefetch"][1]["eagerness"], "moderate");
+        assert_eq!(rules["prefetch"].as_array().unwrap().len(), 2);
+        assert_eq!(rules["prerender"].as_array().unwrap().len(), 1);
+    }
 }
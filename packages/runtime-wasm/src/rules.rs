@@ -0,0 +1,403 @@
+//! Redirect and rewrite rules evaluated before route matching
+//!
+//! A rule's `source` uses the route pattern syntax (`/old/[slug]`,
+//! `/legacy/[...path]`). Its `destination` is compiled with the session ID
+//! template engine (see `template.rs`): `$slug` or `${slug|slugify}` is the
+//! source param, `${?ref}` a query value and `${locale}` the request's
+//! locale. Substituted values are percent-encoded as path segments. Rules
+//! are tried in registration order and the first one whose source and
+//! conditions match wins. The request's query string is carried over to the
+//! destination.
+
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::path::{parse_query, percent_encode_segment};
+use crate::router::{match_segments, parse_pattern, read_segments, write_segments, RouteError, RouteErrorKind, Segment};
+use crate::snapshot::{Reader, SnapshotError, Writer};
+use crate::template::{SessionTemplate, TemplateError};
+use crate::RouteMatch;
+
+/// What a rule does when it matches
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// Send the client to the destination (307, or 308 if permanent)
+    Redirect,
+    /// Serve the destination's route under the original URL
+    Rewrite,
+}
+
+/// Where a rule condition looks for its value
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConditionSource {
+    Header,
+    Cookie,
+}
+
+/// A header or cookie that must be present (and equal `value`, if set)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RuleCondition {
+    #[serde(rename = "type")]
+    source: ConditionSource,
+    key: String,
+    #[serde(default)]
+    value: Option<String>,
+}
+
+/// Redirect or rewrite rule, as loaded from the rules manifest
+#[wasm_bindgen]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuleDefinition {
+    /// Source pattern like "/old-blog/[slug]"
+    source: String,
+    /// Destination template like "/blog/$slug"
+    destination: String,
+    kind: RuleKind,
+    /// Redirects only: 308 instead of 307
+    #[serde(default)]
+    permanent: bool,
+    /// Every condition must hold for the rule to apply
+    #[serde(default)]
+    has: Vec<RuleCondition>,
+}
+
+#[wasm_bindgen]
+impl RuleDefinition {
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: String, destination: String, kind: RuleKind) -> Self {
+        Self {
+            source,
+            destination,
+            kind,
+            permanent: false,
+            has: Vec::new(),
+        }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn destination(&self) -> String {
+        self.destination.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> RuleKind {
+        self.kind
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn permanent(&self) -> bool {
+        self.permanent
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_permanent(&mut self, permanent: bool) {
+        self.permanent = permanent;
+    }
+
+    /// Require a header or cookie; with `value`, it must also be equal
+    pub fn add_condition(&mut self, source: ConditionSource, key: String, value: Option<String>) {
+        self.has.push(RuleCondition { source, key, value });
+    }
+}
+
+/// Headers and cookies of the request being evaluated
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct RequestContext {
+    /// Header values by lowercase name
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    cookies: HashMap<String, String>,
+}
+
+impl RequestContext {
+    /// Parse `{ "headers": {...}, "cookies": {...} }`. Header names are
+    /// case-insensitive; without `cookies`, the `cookie` header is parsed.
+    pub(crate) fn from_json(json: &str) -> Result<Self, String> {
+        if json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        let mut context: RequestContext =
+            serde_json::from_str(json).map_err(|e| format!("Failed to parse request: {}", e))?;
+        context.headers = context
+            .headers
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .collect();
+        if context.cookies.is_empty() {
+            if let Some(header) = context.headers.get("cookie") {
+                context.cookies = header
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                    .collect();
+            }
+        }
+        Ok(context)
+    }
+}
+
+impl RuleCondition {
+    fn matches(&self, request: &RequestContext) -> bool {
+        let actual = match self.source {
+            ConditionSource::Header => request.headers.get(&self.key.to_ascii_lowercase()),
+            ConditionSource::Cookie => request.cookies.get(&self.key),
+        };
+        match (&self.value, actual) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(expected), Some(actual)) => expected == actual,
+        }
+    }
+}
+
+/// A validated rule
+#[derive(Clone, Debug)]
+pub(crate) struct CompiledRule {
+    segments: Vec<Segment>,
+    destination: SessionTemplate,
+    definition: RuleDefinition,
+}
+
+impl CompiledRule {
+    /// Validate the source pattern and compile the destination template
+    pub(crate) fn compile(definition: RuleDefinition) -> Result<Self, RouteError> {
+        let source = definition.source.clone();
+        let parsed = parse_pattern(&source)?;
+        if parsed.slot.is_some() || parsed.intercept_from.is_some() {
+            return Err(RouteError::new(
+                &source,
+                "",
+                RouteErrorKind::InvalidSegment,
+                "rule sources cannot use slots or interception markers",
+            ));
+        }
+        let error = |reason: &str| RouteError::new(&source, "", RouteErrorKind::InvalidDestination, reason);
+        if definition.kind == RuleKind::Rewrite && !definition.destination.starts_with('/') {
+            return Err(error("a rewrite destination must be a path starting with '/'"));
+        }
+        let destination = compile_destination(&definition, &parsed.segments).map_err(|e| {
+            RouteError::new(&source, &e.reference, RouteErrorKind::InvalidDestination, &format!("destination: {}", e.reason))
+        })?;
+        Ok(Self {
+            segments: parsed.segments,
+            destination,
+            definition,
+        })
+    }
+
+    pub(crate) fn kind(&self) -> RuleKind {
+        self.definition.kind
+    }
+
+    /// HTTP status for a redirect
+    pub(crate) fn status(&self) -> u16 {
+        if self.definition.permanent {
+            308
+        } else {
+            307
+        }
+    }
+
+    /// The destination URL if the rule applies to this request
+    pub(crate) fn apply(
+        &self,
+        path_segments: &[&str],
+        query: Option<&str>,
+        locale: Option<&str>,
+        request: &RequestContext,
+        case_sensitive: bool,
+    ) -> Option<String> {
+        if !self.definition.has.iter().all(|c| c.matches(request)) {
+            return None;
+        }
+        let params = match_segments(&self.segments, path_segments, case_sensitive)?;
+        // Optional catch-alls that matched nothing render as ""
+        let query_values = query.map(parse_query).unwrap_or_default();
+        let mut destination = self.destination.render_with(&params, &query_values, locale, encode_path_value);
+        if let Some(query) = query.filter(|q| !q.is_empty()) {
+            destination.push(if destination.contains('?') { '&' } else { '?' });
+            destination.push_str(query);
+        }
+        Some(destination)
+    }
+
+    /// Encode the rule for `AeonRouter::to_bytes`
+    pub(crate) fn write(&self, w: &mut Writer) {
+        let definition = &self.definition;
+        w.str(&definition.source);
+        w.str(&definition.destination);
+        w.bool(definition.kind == RuleKind::Rewrite);
+        w.bool(definition.permanent);
+        w.usize(definition.has.len());
+        for condition in &definition.has {
+            w.bool(condition.source == ConditionSource::Cookie);
+            w.str(&condition.key);
+            w.opt_str(condition.value.as_deref());
+        }
+        write_segments(w, &self.segments);
+    }
+
+    /// Decode a rule written by `write`
+    pub(crate) fn read(r: &mut Reader) -> Result<Self, SnapshotError> {
+        let source = r.str()?;
+        let destination = r.str()?;
+        let kind = if r.bool()? { RuleKind::Rewrite } else { RuleKind::Redirect };
+        let mut definition = RuleDefinition::new(source, destination, kind);
+        definition.permanent = r.bool()?;
+        for _ in 0..r.usize()? {
+            let source = if r.bool()? { ConditionSource::Cookie } else { ConditionSource::Header };
+            definition.add_condition(source, r.str()?, r.opt_str()?);
+        }
+        let segments = read_segments(r)?;
+        let destination = compile_destination(&definition, &segments)
+            .map_err(|e| SnapshotError::Invalid(format!("invalid rule destination: {}", e.reason)))?;
+        Ok(Self {
+            segments,
+            destination,
+            definition,
+        })
+    }
+}
+
+fn compile_destination(definition: &RuleDefinition, segments: &[Segment]) -> Result<SessionTemplate, TemplateError> {
+    let params: Vec<&str> = segments.iter().filter_map(Segment::param_name).collect();
+    SessionTemplate::parse(&definition.destination, &params)
+}
+
+/// Encode a param value for a path, keeping the `/` between catch-all parts
+fn encode_path_value(value: &str) -> String {
+    value.split('/').map(percent_encode_segment).collect::<Vec<_>>().join("/")
+}
+
+/// What `AeonRouter::evaluate` decided for a request
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutcomeKind {
+    /// Respond with a redirect to `location`
+    Redirect,
+    /// One or more rewrites applied; `route_match` is the final route
+    Rewrite,
+    /// No rule applied; `route_match` is the route for the original URL
+    Match,
+    /// No rule applied and no route matched (possibly after rewrites)
+    NotFound,
+}
+
+/// Result of evaluating rules and routes for a request
+#[wasm_bindgen]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RouteOutcome {
+    kind: OutcomeKind,
+    /// Redirect target
+    location: Option<String>,
+    /// Redirect status (307 or 308)
+    status: Option<u16>,
+    /// Path that was matched after rewrites
+    path: String,
+    route_match: Option<RouteMatch>,
+}
+
+impl RouteOutcome {
+    pub(crate) fn redirect(location: String, status: u16, path: String) -> Self {
+        Self {
+            kind: OutcomeKind::Redirect,
+            location: Some(location),
+            status: Some(status),
+            path,
+            route_match: None,
+        }
+    }
+
+    pub(crate) fn matched(path: String, route_match: Option<RouteMatch>, rewritten: bool) -> Self {
+        let kind = match (&route_match, rewritten) {
+            (None, _) => OutcomeKind::NotFound,
+            (Some(_), true) => OutcomeKind::Rewrite,
+            (Some(_), false) => OutcomeKind::Match,
+        };
+        Self {
+            kind,
+            location: None,
+            status: None,
+            path,
+            route_match,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl RouteOutcome {
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> OutcomeKind {
+        self.kind
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn location(&self) -> Option<String> {
+        self.location.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// The URL whose route was matched (the original URL unless rewritten)
+    #[wasm_bindgen(getter)]
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn route_match(&self) -> Option<RouteMatch> {
+        self.route_match.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(source: &str, destination: &str) -> Result<CompiledRule, RouteError> {
+        CompiledRule::compile(RuleDefinition::new(source.into(), destination.into(), RuleKind::Redirect))
+    }
+
+    #[test]
+    fn test_destination_templates() {
+        let apply = |rule: &CompiledRule, path: &[&str], query: Option<&str>| {
+            rule.apply(path, query, Some("de"), &RequestContext::default(), true).unwrap()
+        };
+        // Same grammar as session IDs: `$ab` is its own reference, values are not re-scanned
+        let params = rule("/[a]/[ab]", "/$a/$ab/${a}b/$$a/$").unwrap();
+        assert_eq!(apply(&params, &["$ab", "x y"], None), "/$ab/x%20y/$abb/$a/$");
+
+        let filtered = rule("/p/[title]/[...rest]", "/${title|slugify}/$rest/${?ref}/$locale").unwrap();
+        assert_eq!(apply(&filtered, &["p", "Hello World", "a", "b"], Some("ref=x")), "/hello-world/a/b/x/de?ref=x");
+
+        let error = rule("/[id]", "/x/$idx").err().unwrap();
+        assert_eq!((error.kind(), error.segment()), (RouteErrorKind::InvalidDestination, "idx".to_string()));
+        assert!(rule("/[id]", "/x/${id|upper}").is_err());
+    }
+
+    #[test]
+    fn test_request_context() {
+        let request =
+            RequestContext::from_json(r#"{"headers": {"Accept-Language": "de", "Cookie": "a=1; beta=on"}}"#).unwrap();
+        let header = RuleCondition { source: ConditionSource::Header, key: "accept-language".into(), value: None };
+        let cookie = RuleCondition { source: ConditionSource::Cookie, key: "beta".into(), value: Some("on".into()) };
+        let wrong = RuleCondition { source: ConditionSource::Cookie, key: "a".into(), value: Some("2".into()) };
+        assert!(header.matches(&request));
+        assert!(cookie.matches(&request));
+        assert!(!wrong.matches(&request));
+        assert!(RequestContext::from_json("not json").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Binary container for compiled router snapshots
+//!
+//! Layout: `AERT` magic, format version (u16 LE), payload, then a 64-bit
+//! FNV-1a checksum (LE) of everything before it. Inside the payload,
+//! integers are LEB128 varints and strings are length-prefixed UTF-8.
+//! `AeonRouter::to_bytes` / `from_bytes` define the payload itself.
+
+const MAGIC: &[u8; 4] = b"AERT";
+
+/// Bump whenever the payload encoding changes
+pub(crate) const FORMAT_VERSION: u16 = 2;
+
+/// Why a snapshot was rejected
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SnapshotError {
+    /// Not a snapshot, corrupted, or truncated
+    Invalid(String),
+    /// Written by a runtime with a different format version
+    UnsupportedVersion(u16),
+}
+
+/// Wrap a payload with the header and checksum
+pub(crate) fn seal(payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(payload.len() + 14);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(payload);
+    let checksum = fnv1a(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/// Check the header and checksum and return the payload
+pub(crate) fn open(bytes: &[u8]) -> Result<&[u8], SnapshotError> {
+    if bytes.len() < MAGIC.len() + 2 + 8 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(SnapshotError::Invalid("not an Aeon router snapshot".to_string()));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 8);
+    let mut expected = [0u8; 8];
+    expected.copy_from_slice(checksum);
+    if fnv1a(body) != u64::from_le_bytes(expected) {
+        return Err(SnapshotError::Invalid("checksum mismatch".to_string()));
+    }
+    Ok(&body[MAGIC.len() + 2..])
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0xcbf29ce484222325u64, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
+/// Payload encoder
+#[derive(Default)]
+pub(crate) struct Writer {
+    pub bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.bytes.push(u8::from(value));
+    }
+
+    pub fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    pub fn usize(&mut self, value: usize) {
+        self.varint(value as u64);
+    }
+
+    pub fn str(&mut self, value: &str) {
+        self.usize(value.len());
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn opt_str(&mut self, value: Option<&str>) {
+        match value {
+            None => self.u8(0),
+            Some(value) => {
+                self.u8(1);
+                self.str(value);
+            }
+        }
+    }
+
+    pub fn strs(&mut self, values: &[String]) {
+        self.usize(values.len());
+        for value in values {
+            self.str(value);
+        }
+    }
+
+    pub fn opt_strs(&mut self, values: Option<&[String]>) {
+        match values {
+            None => self.u8(0),
+            Some(values) => {
+                self.u8(1);
+                self.strs(values);
+            }
+        }
+    }
+}
+
+/// Payload decoder; every read fails cleanly on truncated input
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn truncated() -> SnapshotError {
+        SnapshotError::Invalid("unexpected end of snapshot".to_string())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, SnapshotError> {
+        let byte = *self.bytes.get(self.pos).ok_or_else(Self::truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(SnapshotError::Invalid(format!("invalid bool {}", other))),
+        }
+    }
+
+    pub fn varint(&mut self) -> Result<u64, SnapshotError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(SnapshotError::Invalid("varint too long".to_string()))
+    }
+
+    pub fn usize(&mut self) -> Result<usize, SnapshotError> {
+        usize::try_from(self.varint()?).map_err(|_| SnapshotError::Invalid("length out of range".to_string()))
+    }
+
+    pub fn str(&mut self) -> Result<String, SnapshotError> {
+        let len = self.usize()?;
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len()).ok_or_else(Self::truncated)?;
+        let value = std::str::from_utf8(&self.bytes[self.pos..end])
+            .map_err(|_| SnapshotError::Invalid("invalid UTF-8 string".to_string()))?;
+        self.pos = end;
+        Ok(value.to_string())
+    }
+
+    pub fn opt_str(&mut self) -> Result<Option<String>, SnapshotError> {
+        Ok(if self.bool()? { Some(self.str()?) } else { None })
+    }
+
+    pub fn strs(&mut self) -> Result<Vec<String>, SnapshotError> {
+        (0..self.usize()?).map(|_| self.str()).collect()
+    }
+
+    pub fn opt_strs(&mut self) -> Result<Option<Vec<String>>, SnapshotError> {
+        Ok(if self.bool()? { Some(self.strs()?) } else { None })
+    }
+
+    /// Fail if anything is left after the payload
+    pub fn finish(&self) -> Result<(), SnapshotError> {
+        if self.pos == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(SnapshotError::Invalid("trailing bytes after snapshot".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primitives_round_trip() {
+        let mut writer = Writer::default();
+        for value in [0u64, 127, 128, 300, u64::MAX] {
+            writer.varint(value);
+        }
+        writer.str("café");
+        writer.opt_str(None);
+        writer.opt_str(Some(""));
+        writer.bool(true);
+
+        let mut reader = Reader::new(&writer.bytes);
+        for value in [0u64, 127, 128, 300, u64::MAX] {
+            assert_eq!(reader.varint().unwrap(), value);
+        }
+        assert_eq!(reader.str().unwrap(), "café");
+        assert_eq!(reader.opt_str().unwrap(), None);
+        assert_eq!(reader.opt_str().unwrap(), Some(String::new()));
+        assert!(reader.bool().unwrap());
+        reader.finish().unwrap();
+        assert!(reader.u8().is_err());
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let sealed = seal(b"payload");
+        assert_eq!(open(&sealed).unwrap(), b"payload");
+
+        let mut corrupted = sealed.clone();
+        corrupted[7] ^= 1;
+        assert!(matches!(open(&corrupted), Err(SnapshotError::Invalid(_))));
+
+        let mut future = sealed.clone();
+        future[4] = 99;
+        assert_eq!(open(&future), Err(SnapshotError::UnsupportedVersion(99)));
+
+        assert!(open(b"AERT").is_err());
+        assert!(open(&sealed[..sealed.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Speculation Rules generated from the route table
+//!
+//! `AeonRouter::speculation_rules` emits a Speculation Rules document:
+//! static routes become `list` rules with their URLs, and dynamic routes
+//! become `document` rules whose `href_matches` URL patterns are translated
+//! from the route segments (`[slug]` is `:slug`, `[...path]` is `:path+`,
+//! `[[...path]]` is `:path*`). By default static routes are prerendered and
+//! dynamic routes prefetched. A route's `metadata.speculation` overrides
+//! this: `"prefetch"`, `"prerender"`, or `false` to exclude the route, which
+//! also keeps dynamic patterns from covering it. Without it, the
+//! `metadata.prerender` flag decides: `true` prerenders the route and
+//! `false` only prefetches it.
+//!
+//! Only GET routes of the main table without a host pattern are considered;
+//! slot and intercepting routes are not navigation targets of their own.
+
+use wasm_bindgen::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::path::percent_encode_segment;
+use crate::router::{ParamConstraint, Segment, TrailingSlash};
+
+/// How eagerly the browser acts on generated rules
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Eagerness {
+    Immediate,
+    Eager,
+    Moderate,
+    Conservative,
+}
+
+/// What the browser does for a route's links
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SpeculationAction {
+    Prefetch,
+    Prerender,
+}
+
+impl SpeculationAction {
+    /// The action for a route, or `None` if its metadata excludes it
+    pub(crate) fn for_route(metadata: Option<&serde_json::Map<String, Value>>, dynamic: bool) -> Option<Self> {
+        let get = |key: &str| metadata.and_then(|metadata| metadata.get(key));
+        match (get("speculation"), get("prerender")) {
+            (Some(Value::Bool(false)), _) => None,
+            (Some(Value::String(action)), _) if action == "prefetch" => Some(Self::Prefetch),
+            (Some(Value::String(action)), _) if action == "prerender" => Some(Self::Prerender),
+            (_, Some(Value::Bool(true))) => Some(Self::Prerender),
+            (_, Some(Value::Bool(false))) => Some(Self::Prefetch),
+            _ if dynamic => Some(Self::Prefetch),
+            _ => Some(Self::Prerender),
+        }
+    }
+}
+
+/// URLs and URL patterns of one action
+#[derive(Debug, Default)]
+struct ActionRules {
+    urls: Vec<String>,
+    patterns: Vec<String>,
+}
+
+/// Speculation Rules document under construction
+#[derive(Debug, Default)]
+pub(crate) struct SpeculationRules {
+    prefetch: ActionRules,
+    prerender: ActionRules,
+    /// URL patterns of excluded routes
+    excluded: Vec<String>,
+}
+
+impl SpeculationRules {
+    fn action(&mut self, action: SpeculationAction) -> &mut ActionRules {
+        match action {
+            SpeculationAction::Prefetch => &mut self.prefetch,
+            SpeculationAction::Prerender => &mut self.prerender,
+        }
+    }
+
+    pub(crate) fn add_urls(&mut self, action: SpeculationAction, urls: Vec<String>) {
+        let rules = self.action(action);
+        for url in urls {
+            if !rules.urls.contains(&url) {
+                rules.urls.push(url);
+            }
+        }
+    }
+
+    pub(crate) fn add_pattern(&mut self, action: SpeculationAction, pattern: String) {
+        let rules = self.action(action);
+        if !rules.patterns.contains(&pattern) {
+            rules.patterns.push(pattern);
+        }
+    }
+
+    pub(crate) fn exclude(&mut self, pattern: String) {
+        if !self.excluded.contains(&pattern) {
+            self.excluded.push(pattern);
+        }
+    }
+
+    /// The document; actions without rules are omitted
+    pub(crate) fn to_json(&self, eagerness: Eagerness) -> Value {
+        let mut document = serde_json::Map::new();
+        for (key, rules) in [("prefetch", &self.prefetch), ("prerender", &self.prerender)] {
+            let mut entries = Vec::new();
+            if !rules.urls.is_empty() {
+                entries.push(json!({ "source": "list", "urls": rules.urls, "eagerness": eagerness }));
+            }
+            if !rules.patterns.is_empty() {
+                let mut condition = any_href_matches(&rules.patterns);
+                if !self.excluded.is_empty() {
+                    condition = json!({ "and": [condition, { "not": any_href_matches(&self.excluded) }] });
+                }
+                entries.push(json!({ "source": "document", "where": condition, "eagerness": eagerness }));
+            }
+            if !entries.is_empty() {
+                document.insert(key.to_string(), Value::Array(entries));
+            }
+        }
+        Value::Object(document)
+    }
+}
+
+fn any_href_matches(patterns: &[String]) -> Value {
+    json!({ "or": patterns.iter().map(|p| json!({ "href_matches": p })).collect::<Vec<_>>() })
+}
+
+/// URL pattern (as in the URL Pattern API) matching a route's paths.
+///
+/// With `locales`, an optional locale prefix is accepted. Regex constraints
+/// are not translated and match any segment value.
+pub(crate) fn url_pattern(segments: &[Segment], locales: &[String], trailing_slash: TrailingSlash) -> String {
+    let mut pattern = String::new();
+    if !locales.is_empty() {
+        let options: Vec<String> = locales.iter().map(|l| escape_regex(&percent_encode_segment(l))).collect();
+        pattern.push_str(&format!("{{/({})}}?", options.join("|")));
+    }
+    for segment in segments {
+        pattern.push('/');
+        match segment {
+            Segment::Static(value) => pattern.push_str(&escape_pattern(&percent_encode_segment(value))),
+            Segment::Dynamic(name, constraint) => {
+                pattern.push(':');
+                pattern.push_str(name);
+                match constraint {
+                    Some(ParamConstraint::Int) => pattern.push_str(r"(-?\d+)"),
+                    Some(ParamConstraint::Uuid) => pattern.push_str(
+                        "([0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12})",
+                    ),
+                    Some(ParamConstraint::OneOf(options)) => {
+                        let options: Vec<String> =
+                            options.iter().map(|o| escape_regex(&percent_encode_segment(o))).collect();
+                        pattern.push_str(&format!("({})", options.join("|")));
+                    }
+                    Some(ParamConstraint::Pattern(_)) | None => {}
+                }
+            }
+            Segment::CatchAll(name) => pattern.push_str(&format!(":{}+", name)),
+            Segment::OptionalCatchAll(name) => pattern.push_str(&format!(":{}*", name)),
+        }
+    }
+    match (segments.is_empty(), trailing_slash) {
+        (true, _) if locales.is_empty() => pattern.push('/'),
+        (true, _) | (false, TrailingSlash::Ignore) => pattern.push_str("{/}?"),
+        (false, TrailingSlash::Always) => pattern.push('/'),
+        (false, TrailingSlash::Never) => {}
+    }
+    pattern
+}
+
+/// Escape URL pattern syntax in a literal
+fn escape_pattern(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if ":*+?(){}\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape regex syntax in a literal inside a `(...)` group
+fn escape_regex(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if ".*+?()[]{}|^$\\/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::parse_pattern;
+
+    fn pattern(route: &str, locales: &[&str], trailing_slash: TrailingSlash) -> String {
+        let locales: Vec<String> = locales.iter().map(|l| l.to_string()).collect();
+        url_pattern(&parse_pattern(route).unwrap().segments, &locales, trailing_slash)
+    }
+
+    #[test]
+    fn test_url_pattern() {
+        let never = TrailingSlash::Never;
+        assert_eq!(pattern("/", &[], never), "/");
+        assert_eq!(pattern("/(shop)/blog/[slug]", &[], never), "/blog/:slug");
+        assert_eq!(pattern("/api/[...path]", &[], never), "/api/:path+");
+        assert_eq!(pattern("/docs/[[...slug]]", &[], TrailingSlash::Always), "/docs/:slug*/");
+        assert_eq!(pattern("/users/[id:int]", &[], TrailingSlash::Ignore), r"/users/:id(-?\d+){/}?");
+        assert_eq!(pattern("/[lang:(en|pt.br)]/a+b", &[], never), r"/:lang(en|pt\.br)/a\+b");
+        assert_eq!(pattern("/tags/[t:/^x+$/]/café", &[], never), "/tags/:t/caf%C3%A9");
+        assert_eq!(pattern("/about", &["en", "de"], never), "{/(en|de)}?/about");
+        assert_eq!(pattern("/", &["en"], never), "{/(en)}?{/}?");
+    }
+
+    #[test]
+    fn test_document() {
+        let mut rules = SpeculationRules::default();
+        assert_eq!(rules.to_json(Eagerness::Moderate), json!({}));
+
+        rules.add_urls(SpeculationAction::Prerender, vec!["/".into(), "/about".into(), "/".into()]);
+        rules.add_pattern(SpeculationAction::Prefetch, "/blog/:slug".into());
+        rules.exclude("/blog/admin".into());
+        assert_eq!(
+            rules.to_json(Eagerness::Eager),
+            json!({
+                "prefetch": [{
+                    "source": "document",
+                    "where": { "and": [
+                        { "or": [{ "href_matches": "/blog/:slug" }] },
+                        { "not": { "or": [{ "href_matches": "/blog/admin" }] } }
+                    ] },
+                    "eagerness": "eager"
+                }],
+                "prerender": [{ "source": "list", "urls": ["/", "/about"], "eagerness": "eager" }]
+            })
+        );
+
+        let metadata = |value: Value| json!({ "speculation": value }).as_object().cloned();
+        assert_eq!(SpeculationAction::for_route(None, true), Some(SpeculationAction::Prefetch));
+        assert_eq!(SpeculationAction::for_route(None, false), Some(SpeculationAction::Prerender));
+        assert_eq!(SpeculationAction::for_route(metadata(json!(false)).as_ref(), false), None);
+        assert_eq!(
+            SpeculationAction::for_route(metadata(json!("prerender")).as_ref(), true),
+            Some(SpeculationAction::Prerender)
+        );
+
+        // The `prerender` flag applies unless `speculation` is set
+        let flags = |value: Value| value.as_object().cloned();
+        let no_prerender = flags(json!({ "prerender": false }));
+        assert_eq!(SpeculationAction::for_route(no_prerender.as_ref(), false), Some(SpeculationAction::Prefetch));
+        let prerender = flags(json!({ "prerender": true }));
+        assert_eq!(SpeculationAction::for_route(prerender.as_ref(), true), Some(SpeculationAction::Prerender));
+        let both = flags(json!({ "prerender": true, "speculation": "prefetch" }));
+        assert_eq!(SpeculationAction::for_route(both.as_ref(), false), Some(SpeculationAction::Prefetch));
+    }
+}
@@ -0,0 +1,305 @@
+//! Session ID templates
+//!
+//! - `${name}` - a path or host param
+//! - `${?name}` - first value of a query param (empty if absent)
+//! - `${locale}` - the request's locale, unless a param is named `locale`
+//! - `${name|slugify|hash}` - filters applied left to right: `lowercase`,
+//!   `slugify`, `hash` (FNV-1a, 16 hex digits)
+//! - `$name` / `$?name` - short form; the name is the longest run of
+//!   `[A-Za-z0-9_]` after `$`, so `$idx` never means `$id` followed by `x`
+//!   and `$?q-results` is `$?q` followed by `-results`. Names with other
+//!   characters (e.g. a dashed query key) need `${?utm-source}`
+//! - `$$` - a literal `$`
+//!
+//! Templates are compiled once when the route is added; references to
+//! unknown params are rejected there. Rendering is a single pass over the
+//! compiled parts, so substituted values are never scanned for references.
+
+use std::collections::HashMap;
+
+/// Transform applied to a substituted value
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Filter {
+    Lowercase,
+    /// Lowercase, runs of other characters become one `-`
+    Slugify,
+    /// 64-bit FNV-1a as 16 lowercase hex digits
+    Hash,
+}
+
+/// Where a reference takes its value from
+#[derive(Clone, Debug, PartialEq)]
+enum Source {
+    Param(String),
+    Query(String),
+    Locale,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Literal(String),
+    Reference { source: Source, filters: Vec<Filter> },
+}
+
+/// A rejected template, with the offending reference
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TemplateError {
+    pub reference: String,
+    /// True for a well-formed reference to a param the route does not have
+    pub unknown_param: bool,
+    pub reason: String,
+}
+
+/// How separator characters in substituted values are escaped
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct SeparatorEscape {
+    /// Characters to escape; none by default
+    pub separators: String,
+    /// Replaces each separator; `None` percent-encodes it
+    pub replacement: Option<String>,
+}
+
+impl SeparatorEscape {
+    fn apply(&self, value: &str) -> String {
+        if self.separators.is_empty() || !value.contains(|c| self.separators.contains(c)) {
+            return value.to_string();
+        }
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if !self.separators.contains(c) {
+                escaped.push(c);
+            } else if let Some(replacement) = &self.replacement {
+                escaped.push_str(replacement);
+            } else {
+                for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                    escaped.push_str(&format!("%{:02X}", byte));
+                }
+            }
+        }
+        escaped
+    }
+}
+
+/// A compiled session ID template
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SessionTemplate {
+    parts: Vec<Part>,
+}
+
+impl SessionTemplate {
+    /// Compile `template` for a route declaring `params`
+    pub(crate) fn parse(template: &str, params: &[&str]) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('$') {
+            literal.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+
+            let (reference, filters, consumed) = if let Some(after) = after.strip_prefix('$') {
+                literal.push('$');
+                rest = after;
+                continue;
+            } else if let Some(body) = after.strip_prefix('{') {
+                let end = body.find('}').ok_or_else(|| TemplateError {
+                    reference: format!("${}", after),
+                    unknown_param: false,
+                    reason: "unclosed \"${\"".to_string(),
+                })?;
+                let mut pieces = body[..end].split('|');
+                let reference = pieces.next().unwrap_or_default().trim();
+                let filters = pieces
+                    .map(|name| parse_filter(name.trim(), &body[..end]))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (reference, filters, end + 2)
+            } else {
+                let name_start = usize::from(after.starts_with('?'));
+                let name_len = short_name_len(&after[name_start..]);
+                if name_len == 0 {
+                    // A lone `$` is literal
+                    literal.push('$');
+                    rest = after;
+                    continue;
+                }
+                (&after[..name_start + name_len], Vec::new(), name_start + name_len)
+            };
+
+            let source = match reference.strip_prefix('?') {
+                Some(name) if !name.is_empty() => Source::Query(name.to_string()),
+                _ if params.contains(&reference) => Source::Param(reference.to_string()),
+                _ if reference == "locale" => Source::Locale,
+                _ => {
+                    return Err(TemplateError {
+                        unknown_param: !reference.is_empty() && reference != "?",
+                        reason: if reference.is_empty() || reference == "?" {
+                            "empty reference".to_string()
+                        } else {
+                            format!("the route has no param \"{}\"", reference)
+                        },
+                        reference: reference.to_string(),
+                    });
+                }
+            };
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::Reference { source, filters });
+            rest = &after[consumed..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Substitute values; missing params and query values render empty
+    pub(crate) fn render(
+        &self,
+        params: &HashMap<String, String>,
+        query: &HashMap<String, Vec<String>>,
+        locale: Option<&str>,
+        escape: &SeparatorEscape,
+    ) -> String {
+        self.render_with(params, query, locale, |value| escape.apply(value))
+    }
+
+    /// `render` with a custom encoding of each substituted value (applied
+    /// after the filters)
+    pub(crate) fn render_with(
+        &self,
+        params: &HashMap<String, String>,
+        query: &HashMap<String, Vec<String>>,
+        locale: Option<&str>,
+        encode: impl Fn(&str) -> String,
+    ) -> String {
+        let mut result = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => result.push_str(text),
+                Part::Reference { source, filters } => {
+                    let value = match source {
+                        Source::Param(name) => params.get(name).map(String::as_str),
+                        Source::Query(name) => query.get(name).and_then(|values| values.first()).map(String::as_str),
+                        Source::Locale => locale,
+                    };
+                    let value = filters
+                        .iter()
+                        .fold(value.unwrap_or_default().to_string(), |value, filter| filter.apply(&value));
+                    result.push_str(&encode(&value));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Length of the short-form reference name at the start of `s`
+pub(crate) fn short_name_len(s: &str) -> usize {
+    s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(s.len())
+}
+
+fn parse_filter(name: &str, reference: &str) -> Result<Filter, TemplateError> {
+    match name {
+        "lowercase" => Ok(Filter::Lowercase),
+        "slugify" => Ok(Filter::Slugify),
+        "hash" => Ok(Filter::Hash),
+        _ => Err(TemplateError {
+            reference: format!("${{{}}}", reference),
+            unknown_param: false,
+            reason: format!("unknown filter \"{}\" (expected lowercase, slugify or hash)", name),
+        }),
+    }
+}
+
+impl Filter {
+    fn apply(self, value: &str) -> String {
+        match self {
+            Filter::Lowercase => value.to_lowercase(),
+            Filter::Slugify => {
+                let mut slug = String::with_capacity(value.len());
+                for c in value.chars().flat_map(char::to_lowercase) {
+                    if c.is_alphanumeric() {
+                        slug.push(c);
+                    } else if !slug.is_empty() && !slug.ends_with('-') {
+                        slug.push('-');
+                    }
+                }
+                slug.trim_end_matches('-').to_string()
+            }
+            Filter::Hash => {
+                let hash = value.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+                    (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+                });
+                format!("{:016x}", hash)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, params: &[(&str, &str)]) -> String {
+        let names: Vec<&str> = params.iter().map(|(k, _)| *k).collect();
+        let params = params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let query = [("q".to_string(), vec!["Rust Lang".to_string()])].into_iter().collect();
+        SessionTemplate::parse(template, &names)
+            .unwrap()
+            .render(&params, &query, Some("de"), &SeparatorEscape::default())
+    }
+
+    #[test]
+    fn test_single_pass_substitution() {
+        // `$idx` is its own reference and values are never re-scanned
+        assert_eq!(render("$id-$idx", &[("id", "$idx"), ("idx", "2")]), "$idx-2");
+        assert_eq!(render("${id}x-$$id-$", &[("id", "1")]), "1x-$id-$");
+        assert_eq!(render("s-$?q/${?q|slugify}/${?missing}", &[]), "s-Rust Lang/rust-lang/");
+        assert_eq!(render("$locale/${locale}", &[]), "de/de");
+        assert_eq!(render("$locale", &[("locale", "param")]), "param");
+    }
+
+    #[test]
+    fn test_short_form_query_names() {
+        // The short form stops at `-`; dashed keys need braces
+        assert_eq!(render("search-$?q-results", &[]), "search-Rust Lang-results");
+        assert_eq!(
+            SessionTemplate::parse("$?q-results", &[]).unwrap(),
+            SessionTemplate::parse("${?q}-results", &[]).unwrap()
+        );
+        let template = SessionTemplate::parse("${?q-results}", &[]).unwrap();
+        let query = [("q-results".to_string(), vec!["x".to_string()])].into_iter().collect();
+        assert_eq!(template.render(&HashMap::new(), &query, None, &SeparatorEscape::default()), "x");
+    }
+
+    #[test]
+    fn test_filters_and_escaping() {
+        assert_eq!(render("${t|lowercase}", &[("t", "ÄB")]), "äb");
+        assert_eq!(render("${t|slugify}", &[("t", " Hello,  World! ")]), "hello-world");
+        assert_eq!(render("${t|hash}", &[("t", "")]), "cbf29ce484222325");
+        assert_eq!(render("${t|slugify|hash}", &[("t", "A b")]), render("${t|hash}", &[("t", "a-b")]));
+
+        let template = SessionTemplate::parse("docs-$path", &["path"]).unwrap();
+        let params = [("path".to_string(), "a/b:c".to_string())].into_iter().collect();
+        let escape = |separators: &str, replacement: Option<&str>| SeparatorEscape {
+            separators: separators.to_string(),
+            replacement: replacement.map(str::to_string),
+        };
+        let query = HashMap::new();
+        assert_eq!(template.render(&params, &query, None, &escape("", None)), "docs-a/b:c");
+        assert_eq!(template.render(&params, &query, None, &escape("/:", None)), "docs-a%2Fb%3Ac");
+        assert_eq!(template.render(&params, &query, None, &escape("/:", Some("_"))), "docs-a_b_c");
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let error = SessionTemplate::parse("u-$idx", &["id"]).unwrap_err();
+        assert!(error.unknown_param);
+        assert_eq!(error.reference, "idx");
+        assert!(!SessionTemplate::parse("${id", &["id"]).unwrap_err().unknown_param);
+        assert!(!SessionTemplate::parse("${id|upper}", &["id"]).unwrap_err().unknown_param);
+        assert!(!SessionTemplate::parse("${}", &[]).unwrap_err().unknown_param);
+    }
+}